@@ -1,6 +1,12 @@
 pub const FILE_BUFFER_SIZE: usize = 64 * 1024; //64KB
+pub const LARGE_FILE_BUFFER_SIZE: usize = 16 * 1024 * 1024; //16MB
 pub const DD_COMMENT_CHAR: char = ';';
+pub const DEFAULT_HASH_TYPE: &str = "md5";
 
 pub const SMALL_FILE_SIZE_THRESHOLD: u64 = 1024 * 1024; //1MB
 pub const SMALL_FILE_THREADS: usize = 2;
 pub const LARGE_FILE_THREADS: usize = 2;
+//directories read at once while walking, more than the cores pay off on network mounts
+pub const WALK_THREADS: usize = 16;
+
+pub const IGNORE_FILE_NAME: &str = ".sfisumignore";
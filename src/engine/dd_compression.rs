@@ -0,0 +1,135 @@
+use crate::constants::FILE_BUFFER_SIZE;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub const GZIP_SUFFIX: &str = "gz";
+pub const ZSTD_SUFFIX: &str = "zst";
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression of a digest file, appended as an extra extension: 'x.ddmd5.zst'
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_path(dd_file_path: &Path) -> Self {
+        match dd_file_path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case(GZIP_SUFFIX) => Compression::Gzip,
+            Some(e) if e.eq_ignore_ascii_case(ZSTD_SUFFIX) => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    fn from_magic(data: &[u8]) -> Self {
+        if data.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if data.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    pub fn suffix(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some(GZIP_SUFFIX),
+            Compression::Zstd => Some(ZSTD_SUFFIX),
+        }
+    }
+}
+
+/// The digest path without the compression extension - 'x.ddmd5.zst' gives 'x.ddmd5'
+pub fn uncompressed_path(dd_file_path: &Path) -> PathBuf {
+    match Compression::from_path(dd_file_path) {
+        Compression::None => dd_file_path.to_path_buf(),
+        _ => dd_file_path.with_extension(""),
+    }
+}
+
+/// Opens a digest file for reading, decompressing it if it is compressed.
+/// Compression is detected by the extension, or by the magic bytes if the extension doesn't tell.
+pub fn open_dd_file(dd_file_path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let reader = BufReader::with_capacity(FILE_BUFFER_SIZE, File::open(dd_file_path)?);
+    decompressed(reader, dd_file_path)
+}
+
+/// Same as open_dd_file, for a digest file already read into memory
+pub fn open_dd_data<'a>(dd_file_path: &Path, data: &'a [u8]) -> io::Result<Box<dyn BufRead + 'a>> {
+    decompressed(data, dd_file_path)
+}
+
+fn decompressed<'a, R: BufRead + 'a>(
+    mut reader: R,
+    dd_file_path: &Path,
+) -> io::Result<Box<dyn BufRead + 'a>> {
+    let compression = match Compression::from_path(dd_file_path) {
+        Compression::None => Compression::from_magic(reader.fill_buf()?),
+        compression => compression,
+    };
+
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+    })
+}
+
+/// Creates a digest file for writing, compressed according to its extension.
+/// DdFileWriter::finish must be called, otherwise the compressed stream is incomplete.
+pub fn create_dd_file(dd_file_path: &Path) -> io::Result<DdFileWriter> {
+    let writer = BufWriter::with_capacity(FILE_BUFFER_SIZE, File::create(dd_file_path)?);
+
+    Ok(match Compression::from_path(dd_file_path) {
+        Compression::None => DdFileWriter::Plain(writer),
+        Compression::Gzip => {
+            DdFileWriter::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+        }
+        Compression::Zstd => DdFileWriter::Zstd(zstd::Encoder::new(writer, 0)?),
+    })
+}
+
+pub enum DdFileWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl DdFileWriter {
+    pub fn finish(self) -> io::Result<()> {
+        let mut writer = match self {
+            DdFileWriter::Plain(writer) => writer,
+            DdFileWriter::Gzip(encoder) => encoder.finish()?,
+            DdFileWriter::Zstd(encoder) => encoder.finish()?,
+        };
+
+        writer.flush()
+    }
+}
+
+impl Write for DdFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            DdFileWriter::Plain(writer) => writer.write(buf),
+            DdFileWriter::Gzip(encoder) => encoder.write(buf),
+            DdFileWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            DdFileWriter::Plain(writer) => writer.flush(),
+            DdFileWriter::Gzip(encoder) => encoder.flush(),
+            DdFileWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
@@ -1,11 +1,210 @@
 use crate::constants::DD_COMMENT_CHAR;
-use crate::file_rep::file_metadata::FileMetadata;
-use crate::file_rep::file_st::FileSt;
-use crate::file_rep::hash_def::{hash_string_to_type, HashType, HashValue};
-use std::fs::File;
+use crate::engine::dd_compression::{
+    create_dd_file, open_dd_data, open_dd_file, uncompressed_path,
+};
+use crate::engine::dd_header::{DigestHeader, DigestMode, HEADER_GENERATED_AT};
+use crate::engine::dd_signature::sign_dd_file;
+use crate::engine::engine_factory::{
+    hash_algorithm_by_name, hash_algorithm_by_sum_suffix, HashAlgorithm,
+};
+use crate::file_rep::directory_walker::SymlinkPolicy;
+use crate::file_rep::file_metadata::{FileMetadata, UnixMetadata};
+use crate::file_rep::file_st::{FileSt, SymlinkSt};
+use crate::file_rep::hash::hex::bytes_to_hex;
+use crate::file_rep::hash::sha256::HashSHA256;
+use crate::file_rep::hash_def::{HashState, HashValue};
+use crate::util::varint::{read_varint, write_varint};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
 use std::io;
-use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::path::PathBuf;
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Supported digest file formats, chosen by the digest file name
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DigestFormat {
+    /// The sfisum directory digest, see read_native_dd
+    Native,
+    /// md5sum/sha256sum style '<hash>  <path>' lines, without any metadata
+    Coreutils,
+    /// Simple File Verification, '<path> <crc32>' lines, without any metadata
+    Sfv,
+    /// JSON Lines with a versioned schema, see read_json_dd
+    Json,
+    /// Compact binary digest for very large trees, see read_binary_dd
+    Binary,
+}
+
+impl DigestFormat {
+    pub fn from_path(dd_file_path: &Path) -> Self {
+        let dd_file_path = &uncompressed_path(dd_file_path);
+
+        if has_extension(dd_file_path, SFV_SUFFIX) {
+            return DigestFormat::Sfv;
+        }
+
+        if has_extension(dd_file_path, JSON_SUFFIX) {
+            return DigestFormat::Json;
+        }
+
+        if has_extension(dd_file_path, BINARY_SUFFIX) {
+            return DigestFormat::Binary;
+        }
+
+        match sum_suffix_of_path(dd_file_path) {
+            Some(_) => DigestFormat::Coreutils,
+            None => DigestFormat::Native,
+        }
+    }
+}
+
+pub const SFV_SUFFIX: &str = "sfv";
+
+/// Appended to the DD suffix, eg. 'x.ddmd5.jsonl'
+pub const JSON_SUFFIX: &str = "jsonl";
+
+/// Bumped on changes old readers cannot handle. New optional fields don't need a bump.
+/// 2: a checksum record at the end
+/// 3: symlinks and directories as records of their own, instead of arrays in the header
+pub const JSON_SCHEMA_VERSION: u32 = 3;
+
+const JSON_SCHEMA_NAME: &str = "sfisum-digest";
+
+/// Appended to the DD suffix, eg. 'x.ddmd5.bin'
+pub const BINARY_SUFFIX: &str = "bin";
+
+const BINARY_MAGIC: &[u8; 4] = b"SFDD";
+//2: header fields after the algorithm name
+//3: sub-second modification times
+//4: mode, ownership and change time
+//5: symlinks after the entries
+//6: link groups
+//7: directories after the symlinks
+//8: SHA-256 of every byte before it at the end
+const BINARY_VERSION: u8 = 8;
+
+//entry flags
+const BINARY_HAS_METADATA: u8 = 1;
+const BINARY_HAS_MTIME_NANOS: u8 = 2;
+const BINARY_HAS_UNIX: u8 = 4;
+const BINARY_HAS_LINK_GROUP: u8 = 8;
+
+fn has_extension(dd_file_path: &Path, suffix: &str) -> bool {
+    dd_file_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(suffix))
+}
+
+/// Returns the checksum file suffix of a md5sum style file name - both 'x.md5' and 'MD5SUMS' give 'md5'
+pub fn sum_suffix_of_path(dd_file_path: &Path) -> Option<String> {
+    let file_name = uncompressed_path(dd_file_path)
+        .file_name()?
+        .to_str()?
+        .to_lowercase();
+
+    let suffix = match file_name.rsplit_once('.') {
+        Some((_, extension)) => extension,
+        None => file_name.strip_suffix("sums")?,
+    };
+
+    hash_algorithm_by_sum_suffix(suffix).map(|_| suffix.to_string())
+}
+
+/// Everything read from a digest file
+pub struct DigestContents<H: HashValue> {
+    /// Empty for formats that record none
+    pub header: DigestHeader,
+    pub files: Vec<FileSt<H>>,
+    /// Only recorded with SymlinkPolicy::Record, and only by the native, JSON and binary formats
+    pub symlinks: Vec<SymlinkSt>,
+    /// Only recorded on request (see DigestHeader::record_dirs), by the same formats as symlinks
+    pub dirs: Vec<PathBuf>,
+}
+
+/// Reads a digest file of any supported format, see DigestFormat
+pub fn read_dd<H: HashValue>(
+    dd_file_path: &Path,
+    base_path: &Path,
+) -> io::Result<DigestContents<H>> {
+    read_dd_format(dd_file_path, open_dd_file(dd_file_path)?, base_path)
+}
+
+/// Same as read_dd, for a digest file already read into memory (eg. to check its signature)
+pub fn read_dd_data<H: HashValue>(
+    dd_file_path: &Path,
+    data: &[u8],
+    base_path: &Path,
+) -> io::Result<DigestContents<H>> {
+    read_dd_format(dd_file_path, open_dd_data(dd_file_path, data)?, base_path)
+}
+
+//the format is chosen by the file name, the contents come from the reader
+fn read_dd_format<H: HashValue, R: BufRead>(
+    dd_file_path: &Path,
+    reader: R,
+    base_path: &Path,
+) -> io::Result<DigestContents<H>> {
+    match DigestFormat::from_path(dd_file_path) {
+        DigestFormat::Native => read_native_dd(reader, base_path),
+        DigestFormat::Coreutils => read_coreutils_dd(reader, base_path),
+        DigestFormat::Sfv => read_sfv_dd(reader, base_path),
+        DigestFormat::Json => read_json_dd(reader, base_path),
+        DigestFormat::Binary => read_binary_dd(reader, base_path),
+    }
+}
+
+/// Writes a digest file, the format is chosen by the file name (see DigestFormat).
+/// The header, symlinks and directories are written by the formats that can hold them (not md5sum
+/// or SFV files).
+/// With a signing key, a detached signature is written next to it (see sign_dd_file).
+pub fn write_dd<H: HashValue>(
+    snapshot: &Vec<&FileSt<H>>,
+    symlinks: &[SymlinkSt],
+    dirs: &[PathBuf],
+    dd_file_path: &Path,
+    base_path: &Path,
+    header: &DigestHeader,
+    signing_key: Option<&SigningKey>,
+) -> io::Result<()> {
+    write_dd_format(snapshot, symlinks, dirs, dd_file_path, base_path, header)?;
+
+    match signing_key {
+        Some(key) => sign_dd_file(dd_file_path, key),
+        None => Ok(()),
+    }
+}
+
+fn write_dd_format<H: HashValue>(
+    snapshot: &Vec<&FileSt<H>>,
+    symlinks: &[SymlinkSt],
+    dirs: &[PathBuf],
+    dd_file_path: &Path,
+    base_path: &Path,
+    header: &DigestHeader,
+) -> io::Result<()> {
+    match DigestFormat::from_path(dd_file_path) {
+        DigestFormat::Native => {
+            write_native_dd(snapshot, symlinks, dirs, dd_file_path, base_path, header)
+        }
+        DigestFormat::Coreutils => write_coreutils_dd(snapshot, dd_file_path, base_path),
+        DigestFormat::Sfv => write_sfv_dd(snapshot, dd_file_path, base_path),
+        DigestFormat::Json => {
+            write_json_dd(snapshot, symlinks, dirs, dd_file_path, base_path, header)
+        }
+        DigestFormat::Binary => {
+            write_binary_dd(snapshot, symlinks, dirs, dd_file_path, base_path, header)
+        }
+    }
+}
+
+//Path of a file or link relative to the base path, as written to a digest
+fn relative_path_string(path: &Path, base_path: &Path) -> io::Result<String> {
+    let rel_path = path
+        .strip_prefix(base_path)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    Ok(rel_path.to_string_lossy().to_string())
+}
 
 ///Returns a DirectorySnapshot from a digest file. The DirectorySnapshot will be filled
 /// with all the information from the digest file.
@@ -19,27 +218,47 @@ use std::path::PathBuf;
 ///
 /// File format, where C is the comment character:
 /// C Directory digest generated at {time}
+/// C Format: <major version>[.<minor version>]
+/// C Capabilities: <comma separated flags, see NATIVE_CAPABILITIES>
+/// C Host: <host name>
+/// C Base directory: <path of the directory when the digest was made>
+/// C Generator: sfisum <version>
+/// C Mode: <generate|validate|fast refresh|full refresh|migrate>
 /// <any other comments>
 /// C Hash: <hash type>
-/// C Size: 2999880, Last modified: 1733589895
+/// C Size: 2999880, Last modified: 1733589895.123456789
 /// 4534bfadb395bc299157d52eac16c368 *\Desktop\test\text.docx
 /// C Size: 2999880, Last modified: 1733589895
 /// 4534bfadb395bc299157d52eac16c368 *\Desktop\test\text.docx
 /// ...
 ///
+/// C Symlink: "<path>" -> "<target>"
+/// ...
+/// C Directory: "<path>"
+/// ...
 /// <any other comments>
-pub fn read_dd<H: HashValue>(
-    dd_file_path: &PathBuf,
-    base_path: &PathBuf,
-) -> io::Result<Vec<FileSt<H>>> {
-    let file = File::open(&dd_file_path)?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
+/// C Checksum: sha256 <sha256 of every byte above>
+///
+/// Entry lines without a metadata line are read as entries with unknown metadata, and unknown
+/// metadata fields are ignored. Modification times without a fraction (format 1 and older) are
+/// compared in whole seconds. Symlink paths and targets are JSON strings, so they may hold any
+/// character.
+/// The checksum line is optional, but if present the digest is refused when it doesn't match.
+/// Digests without a format line predate it, and are read as format 0. Header lines other
+/// than the hash type are optional.
+fn read_native_dd<H: HashValue, R: BufRead>(
+    reader: R,
+    base_path: &Path,
+) -> io::Result<DigestContents<H>> {
+    let mut lines = ChecksummedLines::new(reader, native_checksum_trailer);
 
-    //Skip all lines until 'C Hash: <hash type>'
+    //Skip all lines until 'C Hash: <hash type>', reading the format header on the way
     let mut hash_type_line = None;
+    let mut capabilities = Vec::new();
+    let mut header = DigestHeader::default();
     let mut current_line = 0;
-    while let Some(Ok(line)) = lines.next() {
+    for line in lines.by_ref() {
+        let line = line?;
         current_line += 1;
         if line.starts_with(&format!("{} ", DD_COMMENT_CHAR)) {
             //Get the line, and remove the comment and space
@@ -48,6 +267,22 @@ pub fn read_dd<H: HashValue>(
                     hash_type_line = Some(line);
                     break;
                 }
+
+                if let Some(version) = hash_str.strip_prefix("Format: ") {
+                    check_native_format_version(version, current_line)?;
+                }
+
+                if let Some(flags) = hash_str.strip_prefix("Capabilities: ") {
+                    capabilities = flags.split(',').map(|f| f.trim().to_string()).collect();
+                }
+
+                if let Some(title) = hash_str.strip_prefix(NATIVE_TITLE_PREFIX) {
+                    if let Some((generated_at, _)) = title.split_once(" containing ") {
+                        header.generated_at = Some(generated_at.to_string());
+                    }
+                } else if let Some((key, value)) = hash_str.split_once(": ") {
+                    header.set_field(key, value);
+                }
             }
         }
     }
@@ -80,48 +315,51 @@ pub fn read_dd<H: HashValue>(
         ));
     }
 
+    let has_metadata = capabilities.iter().any(|c| c == CAPABILITY_METADATA);
     let mut files = Vec::new();
+    let mut symlinks = Vec::new();
+    let mut dirs = Vec::new();
 
     //Parse (metadata x file) entries
-    while let Some(Ok(line)) = lines.next() {
+    while let Some(line) = lines.next() {
+        let line = line?;
         current_line += 1;
-        if line.starts_with(&format!("{} ", DD_COMMENT_CHAR)) {
+        if let Some(symlink) = line.strip_prefix(&format!("{} {}", DD_COMMENT_CHAR, SYMLINK_PREFIX))
+        {
+            let (path, target) = parse_symlink_line(symlink).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid symlink entry at line {}", current_line),
+                )
+            })?;
+            symlinks.push(SymlinkSt::new(
+                base_path.join(normalize_separators(&path)),
+                PathBuf::from(target),
+            ));
+        } else if let Some(dir) =
+            line.strip_prefix(&format!("{} {}", DD_COMMENT_CHAR, DIRECTORY_PREFIX))
+        {
+            let path: String = serde_json::from_str(dir).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid directory entry at line {}", current_line),
+                )
+            })?;
+            dirs.push(base_path.join(normalize_separators(&path)));
+        } else if line.starts_with(&format!("{} ", DD_COMMENT_CHAR)) {
             //Try to parse metadata
             if let Some(metadata_str) = line.strip_prefix(&format!("{} ", DD_COMMENT_CHAR)) {
                 if let Ok(metadata) = FileMetadata::new_from_string(metadata_str) {
                     //Parse file entry on the next line
-                    if let Some(Ok(file_line)) = lines.next() {
+                    if let Some(file_line) = lines.next().transpose()? {
                         current_line += 1;
                         //Must not be a comment or empty
                         if !file_line.starts_with(DD_COMMENT_CHAR) && !file_line.trim().is_empty() {
-                            //Split at the first space to get the file path and hash
-                            if let Some((hash_str, path_str)) = file_line.split_once(' ') {
-                                if let Some(hash) = H::new_from_string(hash_str) {
-                                    //Remove the '*'
-                                    let path_str = if path_str.starts_with('*') {
-                                        let path = &path_str[1..];
-
-                                        //Replace separators if the file was generated on windows/unix fs
-                                        #[cfg(windows)]
-                                        {
-                                            path.replace('/', "\\")
-                                        }
-                                        #[cfg(unix)]
-                                        {
-                                            path.replace('\\', "/")
-                                        }
-                                        //note: canonicalize()?
-                                    } else {
-                                        path_str.to_string()
-                                    };
-
-                                    files.push(FileSt::new(
-                                        base_path.join(&path_str),
-                                        Some(hash),
-                                        metadata,
-                                    ));
-                                    continue;
-                                }
+                            if let Some(file) =
+                                parse_entry_line(&file_line, base_path, Some(metadata))
+                            {
+                                files.push(file);
+                                continue;
                             }
                         }
                     }
@@ -135,11 +373,23 @@ pub fn read_dd<H: HashValue>(
                     ));
                 }
             }
+        } else if let Some(file) = parse_entry_line(&line, base_path, None) {
+            //Entry without metadata
+            if has_metadata {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("File entry without metadata at line {}", current_line),
+                ));
+            }
+            files.push(file);
         }
         //Skip any other lines
     }
 
-    if files.is_empty() {
+    //a digest that announces a checksum but has none was cut off
+    lines.verify(capabilities.iter().any(|c| c == CAPABILITY_CHECKSUM))?;
+
+    if files.is_empty() && dirs.is_empty() {
         return Err(io::Error::new(
             io::ErrorKind::InvalidData,
             format!(
@@ -149,49 +399,344 @@ pub fn read_dd<H: HashValue>(
         ));
     }
 
-    Ok(files)
+    Ok(DigestContents {
+        header,
+        files,
+        symlinks,
+        dirs,
+    })
 }
 
-pub fn write_dd<H: HashValue>(
-    snapshot: &Vec<&FileSt<H>>,
-    dd_file_path: &PathBuf,
-    base_path: &PathBuf,
-) -> io::Result<()> {
-    let file = File::create(dd_file_path)?;
-    let mut writer = BufWriter::new(file);
+const NATIVE_TITLE_PREFIX: &str = "Directory digest generated at ";
+const SYMLINK_PREFIX: &str = "Symlink: ";
+//followed by the path as a JSON string
+const DIRECTORY_PREFIX: &str = "Directory: ";
 
-    //Title
-    writeln!(
-        writer,
-        "{} Directory digest generated at {} containing {} entries",
-        DD_COMMENT_CHAR,
-        chrono::Local::now().to_rfc3339(),
-        snapshot.len()
-    )?;
+//'"<path>" -> "<target>"', both JSON strings
+fn parse_symlink_line(line: &str) -> Option<(String, String)> {
+    let mut stream = serde_json::Deserializer::from_str(line).into_iter::<String>();
+    let path = stream.next()?.ok()?;
+    let target = line[stream.byte_offset()..].strip_prefix(" -> ")?;
+    let target = serde_json::from_str(target).ok()?;
 
-    //Hash signature
-    writeln!(
-        writer,
-        "{} Hash: {}",
-        DD_COMMENT_CHAR,
-        H::signature_to_string()
-    )?;
+    Some((path, target))
+}
 
-    let base_path = base_path.as_path();
+/// Major version of the native format, bumped on changes that older readers would misparse.
+/// Additions that older readers can skip (new comment lines, new metadata fields) don't need a bump.
+/// 2: modification times with a fraction of a second
+pub const NATIVE_FORMAT_VERSION: u32 = 2;
 
-    for file in snapshot.iter() {
-        //Metadata comment
-        writeln!(writer, "{} {}", DD_COMMENT_CHAR, file.metadata.to_string())?;
+//every entry has a metadata line
+const CAPABILITY_METADATA: &str = "metadata";
+//the digest ends with a checksum line
+const CAPABILITY_CHECKSUM: &str = "checksum";
 
-        //Split path into relative path
+/// Capability flags this version writes. Unknown flags of newer versions are ignored.
+pub const NATIVE_CAPABILITIES: &[&str] = &[CAPABILITY_METADATA, CAPABILITY_CHECKSUM];
+
+fn check_native_format_version(version: &str, line: usize) -> io::Result<()> {
+    let major = version
+        .trim()
+        .split('.')
+        .next()
+        .and_then(|major| major.parse::<u32>().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid digest format version at line {}", line),
+            )
+        })?;
+
+    if major > NATIVE_FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "The digest file uses format version {}, but this version of sfisum only reads up to version {}. Please update sfisum",
+                major, NATIVE_FORMAT_VERSION
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+const CHECKSUM_TRAILER_PREFIX: &str = "Checksum: sha256 ";
+
+//'C Checksum: sha256 <hex>'
+fn native_checksum_trailer(line: &str) -> Option<String> {
+    line.strip_prefix(DD_COMMENT_CHAR)
+        .and_then(|c| c.strip_prefix(' '))
+        .and_then(|c| c.strip_prefix(CHECKSUM_TRAILER_PREFIX))
+        .map(str::to_string)
+}
+
+/// Lines of a text digest, hashed as they are read. The checksum trailer (the line parse_trailer
+/// returns a checksum for) covers every byte before it, and is not returned as a line.
+struct ChecksummedLines<R: BufRead> {
+    reader: R,
+    parse_trailer: fn(&str) -> Option<String>,
+    state: <HashSHA256 as HashValue>::State,
+    trailer: Option<String>,
+    content_after_trailer: bool,
+}
+
+impl<R: BufRead> ChecksummedLines<R> {
+    fn new(reader: R, parse_trailer: fn(&str) -> Option<String>) -> Self {
+        ChecksummedLines {
+            reader,
+            parse_trailer,
+            state: HashSHA256::new_state(),
+            trailer: None,
+            content_after_trailer: false,
+        }
+    }
+
+    /// Reads the rest of the file, then checks the trailer. Digests without a trailer (written
+    /// before it existed) are accepted, unless it is required.
+    fn verify(mut self, required: bool) -> io::Result<()> {
+        for line in self.by_ref() {
+            line?;
+        }
+
+        let expected = match self.trailer {
+            Some(expected) => expected,
+            None if required => return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Digest file is corrupted: its checksum line is missing, the file may be truncated",
+            )),
+            None => return Ok(()),
+        };
+
+        if self.content_after_trailer {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Digest file is corrupted: it has content after its checksum line",
+            ));
+        }
+
+        let actual = HashSHA256::from_state(self.state).to_string();
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Digest file is corrupted: its checksum is {}, but its content hashes to {}. \
+                     If it was edited on purpose, remove the checksum line",
+                    expected, actual
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for ChecksummedLines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+
+            if self.trailer.is_some() {
+                if !line.trim().is_empty() {
+                    self.content_after_trailer = true;
+                }
+                continue;
+            }
+
+            let content = line.trim_end_matches(['\n', '\r']);
+            if let Some(checksum) = (self.parse_trailer)(content) {
+                self.trailer = Some(checksum);
+                continue;
+            }
+
+            self.state.update(line.as_bytes());
+            return Some(Ok(content.to_string()));
+        }
+    }
+}
+
+/// Hashes everything written through it, for the digest checksum trailers
+struct ChecksumWriter<W: Write> {
+    writer: W,
+    state: <HashSHA256 as HashValue>::State,
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.state.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+//Parses '<hash> *<path>', returns None if the line is not a valid entry
+fn parse_entry_line<H: HashValue>(
+    line: &str,
+    base_path: &Path,
+    metadata: Option<FileMetadata>,
+) -> Option<FileSt<H>> {
+    //Must not be a comment or empty
+    if line.starts_with(DD_COMMENT_CHAR) || line.trim().is_empty() {
+        return None;
+    }
+
+    //Split at the first space to get the file path and hash
+    let (hash_str, path_str) = line.split_once(' ')?;
+    let hash = H::new_from_string(hash_str)?;
+
+    //Remove the '*'
+    let path_str = match path_str.strip_prefix('*') {
+        Some(path) => normalize_separators(path),
+        None => path_str.to_string(),
+    };
+
+    Some(FileSt::new(base_path.join(&path_str), Some(hash), metadata))
+}
+
+//Replace separators if the file was generated on windows/unix fs
+fn normalize_separators(path: &str) -> String {
+    #[cfg(windows)]
+    {
+        path.replace('/', "\\")
+    }
+    #[cfg(unix)]
+    {
+        path.replace('\\', "/")
+    }
+    //note: canonicalize()?
+}
+
+/// Reads a md5sum/sha256sum style checksum file:
+///
+/// <hash>  <path>     (text mode)
+/// <hash> *<path>     (binary mode)
+/// \<hash>  <escaped path>   (path contains '\' or a newline)
+///
+/// These files record no metadata, and the hash type is only known from the file name.
+fn read_coreutils_dd<H: HashValue, R: BufRead>(
+    reader: R,
+    base_path: &Path,
+) -> io::Result<DigestContents<H>> {
+    let mut files = Vec::new();
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (escaped, line) = match line.strip_prefix('\\') {
+            Some(line) => (true, line),
+            None => (false, line.as_str()),
+        };
+
+        let entry = line.split_once(' ').and_then(|(hash_str, path_str)| {
+            //text mode has a second space, binary mode a '*'
+            let path_str = path_str
+                .strip_prefix(' ')
+                .or_else(|| path_str.strip_prefix('*'))?;
+            let path_str = if escaped {
+                unescape_coreutils_path(path_str)?
+            } else {
+                normalize_separators(path_str)
+            };
+
+            Some((H::new_from_string(hash_str)?, path_str))
+        });
+
+        match entry {
+            Some((hash, path_str)) => {
+                files.push(FileSt::new(base_path.join(path_str), Some(hash), None))
+            }
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Invalid {} checksum line at line {}",
+                        H::signature_to_string(),
+                        line_index + 1
+                    ),
+                ))
+            }
+        }
+    }
+
+    if files.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No checksum lines found in file",
+        ));
+    }
+
+    //Checksum files record nothing but the hashes
+    Ok(DigestContents {
+        header: DigestHeader::default(),
+        files,
+        symlinks: Vec::new(),
+        dirs: Vec::new(),
+    })
+}
+
+fn unescape_coreutils_path(path: &str) -> Option<String> {
+    let mut result = String::with_capacity(path.len());
+    let mut chars = path.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next()? {
+            '\\' => result.push('\\'),
+            'n' => result.push('\n'),
+            'r' => result.push('\r'),
+            _ => return None,
+        }
+    }
+
+    Some(result)
+}
+
+/// Writes a md5sum/sha256sum compatible checksum file, so 'md5sum -c' can check the directory.
+/// Multi-hash digests are written one checksum type per file, chosen by the file name.
+fn write_coreutils_dd<H: HashValue>(
+    snapshot: &Vec<&FileSt<H>>,
+    dd_file_path: &Path,
+    base_path: &Path,
+) -> io::Result<()> {
+    let sum_suffix = sum_suffix_of_path(dd_file_path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "BUG: Checksum file name has no checksum suffix",
+        )
+    })?;
+
+    let mut writer = create_dd_file(dd_file_path)?;
+
+    for file in snapshot.iter() {
         let rel_path = file
             .path
             .strip_prefix(base_path)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
+        //coreutils on every platform understands '/'
         let path_str = rel_path.to_string_lossy();
+        #[cfg(windows)]
+        let path_str = path_str.replace('\\', "/");
 
-        //Get hash
         let hash_str = file
             .calculated_hash
             .as_ref()
@@ -201,34 +746,1649 @@ pub fn write_dd<H: HashValue>(
                     "BUG: File entry missing hash value",
                 )
             })?
-            .to_string();
+            .sum_string(&sum_suffix)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "{} digests cannot be written as '{}' checksum files",
+                        H::signature_to_string(),
+                        sum_suffix
+                    ),
+                )
+            })?;
 
-        writeln!(writer, "{} *{}", hash_str, path_str)?;
+        //Same escaping as coreutils: the line gets a '\\' prefix, and '\\', newlines are escaped
+        if path_str.contains(['\\', '\n', '\r']) {
+            let escaped = path_str
+                .replace('\\', "\\\\")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            writeln!(writer, "\\{} *{}", hash_str, escaped)?;
+        } else {
+            writeln!(writer, "{} *{}", hash_str, path_str)?;
+        }
     }
 
-    writer.flush()?;
-    Ok(())
+    writer.finish()
 }
 
-pub fn parse_dd_hash_type(dd_file_path: &PathBuf) -> Option<HashType> {
-    let file = File::open(&dd_file_path).ok()?;
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
+/// Reads a SFV file:
+///
+/// ; <any comments>
+/// <path> <crc32>
+///
+/// The path is everything before the last space, so it may contain spaces itself.
+fn read_sfv_dd<H: HashValue, R: BufRead>(
+    reader: R,
+    base_path: &Path,
+) -> io::Result<DigestContents<H>> {
+    let mut files = Vec::new();
 
-    //Skip all lines until 'C Hash: <hash type>'
-    while let Some(Ok(line)) = lines.next() {
-        if line.starts_with(&format!("{} ", DD_COMMENT_CHAR)) {
-            //Get the line, and remove the comment and space
-            if let Some(hash_str) = line.strip_prefix(&format!("{} ", DD_COMMENT_CHAR)) {
-                if hash_str.starts_with("Hash: ") {
-                    // Extract the hash type
-                    if let Some(hash_type_str) = hash_str.strip_prefix("Hash: ") {
-                        return hash_string_to_type(hash_type_str);
-                    }
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() || line.starts_with(DD_COMMENT_CHAR) {
+            continue;
+        }
+
+        let entry = line
+            .trim_end()
+            .rsplit_once(' ')
+            .and_then(|(path_str, hash_str)| {
+                let path_str = path_str.trim_end();
+                if path_str.is_empty() {
+                    return None;
                 }
+
+                Some((
+                    H::new_from_string(hash_str)?,
+                    normalize_separators(path_str),
+                ))
+            });
+
+        match entry {
+            Some((hash, path_str)) => {
+                files.push(FileSt::new(base_path.join(path_str), Some(hash), None))
+            }
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid SFV line at line {}", line_index + 1),
+                ))
             }
         }
     }
 
-    None
+    if files.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No checksum lines found in SFV file",
+        ));
+    }
+
+    Ok(DigestContents {
+        header: DigestHeader::default(),
+        files,
+        symlinks: Vec::new(),
+        dirs: Vec::new(),
+    })
+}
+
+fn write_sfv_dd<H: HashValue>(
+    snapshot: &Vec<&FileSt<H>>,
+    dd_file_path: &Path,
+    base_path: &Path,
+) -> io::Result<()> {
+    let mut writer = create_dd_file(dd_file_path)?;
+
+    writeln!(
+        writer,
+        "{} Generated by sfisum at {}",
+        DD_COMMENT_CHAR,
+        chrono::Local::now().to_rfc3339()
+    )?;
+
+    for file in snapshot.iter() {
+        let rel_path = file
+            .path
+            .strip_prefix(base_path)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let hash_str = file
+            .calculated_hash
+            .as_ref()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "BUG: File entry missing hash value",
+                )
+            })?
+            .to_string();
+
+        //SFV checksums are conventionally upper case
+        writeln!(
+            writer,
+            "{} {}",
+            rel_path.to_string_lossy(),
+            hash_str.to_uppercase()
+        )?;
+    }
+
+    writer.finish()
+}
+
+//First line of a JSON digest
+#[derive(Serialize, Deserialize)]
+struct JsonHeader {
+    schema: String,
+    version: u32,
+    algorithm: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    generated_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_directory: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    generator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symlink_policy: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ignore_patterns: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    one_file_system: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    record_dirs: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    record_link_groups: Option<bool>,
+    //only read, version 1 and 2 digests hold the symlinks and directories here
+    #[serde(default, skip_serializing)]
+    symlinks: Vec<JsonSymlink>,
+    #[serde(default, skip_serializing)]
+    directories: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonSymlink {
+    path: String,
+    target: String,
+}
+
+//Symlink and directory lines of a JSON digest (version 3 and newer), between the header and the
+//file entries
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonRecord {
+    Symlink(JsonSymlink),
+    Dir { path: String },
+}
+
+//Last line of a JSON digest (version 2 and newer), the SHA-256 of every byte before it
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonChecksum {
+    checksum: String,
+}
+
+fn json_checksum_trailer(line: &str) -> Option<String> {
+    if !line.trim_start().starts_with("{\"checksum\"") {
+        return None;
+    }
+
+    serde_json::from_str::<JsonChecksum>(line)
+        .ok()
+        .map(|trailer| trailer.checksum)
+}
+
+//Every other line of a JSON digest
+#[derive(Serialize, Deserialize)]
+struct JsonEntry {
+    path: String,
+    hash: String,
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    mtime: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mtime_nsec: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ctime: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ctime_nsec: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    link_group: Option<u64>,
+}
+
+/// Reads a JSON Lines digest:
+///
+/// {"schema":"sfisum-digest","version":3,"algorithm":"md5","generated_at":"<rfc3339>","host":...,
+///  "base_directory":...,"generator":"sfisum <version>","mode":"fast refresh","symlink_policy":"record",
+///  "ignore_patterns":["*.tmp","!keep.tmp"],"one_file_system":true,"record_dirs":true,
+///  "record_link_groups":true}
+/// {"type":"symlink","path":"dir/link","target":"../file.txt"}
+/// {"type":"dir","path":"dir/empty"}
+/// ...
+/// {"path":"dir/file.txt","hash":"4534bfadb395bc299157d52eac16c368","size":2999880,"mtime":1733589895,"mtime_nsec":123456789}
+/// ...
+/// {"checksum":"<sha256 of every byte above>"}
+///
+/// Paths are relative and always use '/'. 'mtime' is in seconds since the unix epoch, 'size' and
+/// 'mtime' are null when unknown. 'mtime_nsec' (nanoseconds past 'mtime') is optional, without it
+/// modification times are compared in whole seconds. 'mode' (st_mode), 'uid', 'gid', 'ctime' and
+/// 'ctime_nsec' are only present for digests that record them. Files with the same 'link_group'
+/// are hardlinks of one file. Unknown fields are ignored. Lines without a 'type' are file entries,
+/// version 1 and 2 digests hold the symlinks and directories in 'symlinks' and 'directories'
+/// arrays of the header instead. The checksum record is required from version 2 on, and the
+/// digest is refused when it doesn't match.
+fn read_json_dd<H: HashValue, R: BufRead>(
+    reader: R,
+    base_path: &Path,
+) -> io::Result<DigestContents<H>> {
+    let mut checksummed = ChecksummedLines::new(reader, json_checksum_trailer);
+    let mut lines = checksummed
+        .by_ref()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()));
+
+    let invalid_line = |line_index: usize, e: serde_json::Error| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid JSON digest line at line {}: {}", line_index + 1, e),
+        )
+    };
+
+    let header = match lines.next() {
+        Some((line_index, line)) => {
+            serde_json::from_str::<JsonHeader>(&line?).map_err(|e| invalid_line(line_index, e))?
+        }
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Empty JSON digest file",
+            ))
+        }
+    };
+
+    read_json_header::<H>(&header)?;
+    let checksum_required = header.version >= 2;
+
+    let mut symlinks: Vec<SymlinkSt> = header
+        .symlinks
+        .into_iter()
+        .map(|symlink| {
+            SymlinkSt::new(
+                base_path.join(normalize_separators(&symlink.path)),
+                PathBuf::from(symlink.target),
+            )
+        })
+        .collect();
+
+    let mut dirs: Vec<PathBuf> = header
+        .directories
+        .iter()
+        .map(|dir| base_path.join(normalize_separators(dir)))
+        .collect();
+
+    let header = DigestHeader {
+        generated_at: header.generated_at,
+        host: header.host,
+        base_directory: header.base_directory,
+        generator: header.generator,
+        mode: header.mode.as_deref().and_then(DigestMode::parse),
+        symlink_policy: header
+            .symlink_policy
+            .as_deref()
+            .and_then(SymlinkPolicy::parse),
+        ignore_patterns: header.ignore_patterns,
+        one_file_system: header.one_file_system,
+        record_dirs: header.record_dirs,
+        record_link_groups: header.record_link_groups,
+    };
+
+    let mut files = Vec::new();
+
+    for (line_index, line) in lines {
+        let line = line?;
+
+        //a file entry with '"type"' in its path has it escaped
+        if line.contains("\"type\"") {
+            match serde_json::from_str(&line).map_err(|e| invalid_line(line_index, e))? {
+                JsonRecord::Symlink(symlink) => symlinks.push(SymlinkSt::new(
+                    base_path.join(normalize_separators(&symlink.path)),
+                    PathBuf::from(symlink.target),
+                )),
+                JsonRecord::Dir { path } => {
+                    dirs.push(base_path.join(normalize_separators(&path)))
+                }
+            }
+            continue;
+        }
+
+        let entry: JsonEntry =
+            serde_json::from_str(&line).map_err(|e| invalid_line(line_index, e))?;
+
+        let hash = H::new_from_string(&entry.hash).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid hash at line {}", line_index + 1),
+            )
+        })?;
+
+        let mut metadata = match (entry.size, entry.mtime, entry.mtime_nsec) {
+            (Some(size), Some(mtime), Some(nsec)) if nsec < 1_000_000_000 => {
+                Some(FileMetadata::new(
+                    std::time::UNIX_EPOCH + std::time::Duration::new(mtime, nsec),
+                    size,
+                ))
+            }
+            (Some(size), Some(mtime), None) => Some(FileMetadata::new_secs(mtime, size)),
+            (Some(_), Some(_), Some(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid mtime_nsec at line {}", line_index + 1),
+                ))
+            }
+            _ => None,
+        };
+
+        if let (Some(metadata), Some(mode), Some(uid), Some(gid), Some(ctime)) =
+            (&mut metadata, entry.mode, entry.uid, entry.gid, entry.ctime)
+        {
+            let ctime_nsec = entry.ctime_nsec.unwrap_or(0);
+            if ctime_nsec >= 1_000_000_000 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid ctime_nsec at line {}", line_index + 1),
+                ));
+            }
+            metadata.unix = Some(UnixMetadata {
+                mode,
+                uid,
+                gid,
+                changed: std::time::UNIX_EPOCH + std::time::Duration::new(ctime, ctime_nsec),
+            });
+        }
+        if let Some(metadata) = &mut metadata {
+            metadata.link_group = entry.link_group;
+        }
+
+        files.push(FileSt::new(
+            base_path.join(normalize_separators(&entry.path)),
+            Some(hash),
+            metadata,
+        ));
+    }
+
+    checksummed.verify(checksum_required)?;
+
+    if files.is_empty() && dirs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No file entries found in JSON digest",
+        ));
+    }
+
+    Ok(DigestContents {
+        header,
+        files,
+        symlinks,
+        dirs,
+    })
+}
+
+fn read_json_header<H: HashValue>(header: &JsonHeader) -> io::Result<()> {
+    if header.schema != JSON_SCHEMA_NAME {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unknown JSON digest schema '{}'", header.schema),
+        ));
+    }
+
+    if header.version > JSON_SCHEMA_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "JSON digest schema version {} is newer than the supported version {}",
+                header.version, JSON_SCHEMA_VERSION
+            ),
+        ));
+    }
+
+    if !H::parse_hash_type_string(&header.algorithm) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported hash type '{}'", header.algorithm),
+        ));
+    }
+
+    Ok(())
+}
+
+fn write_json_dd<H: HashValue>(
+    snapshot: &Vec<&FileSt<H>>,
+    symlinks: &[SymlinkSt],
+    dirs: &[PathBuf],
+    dd_file_path: &Path,
+    base_path: &Path,
+    header: &DigestHeader,
+) -> io::Result<()> {
+    let mut writer = ChecksumWriter {
+        writer: create_dd_file(dd_file_path)?,
+        state: HashSHA256::new_state(),
+    };
+
+    let header = JsonHeader {
+        schema: JSON_SCHEMA_NAME.to_string(),
+        version: JSON_SCHEMA_VERSION,
+        algorithm: H::signature_to_string().to_string(),
+        generated_at: header.generated_at.clone(),
+        host: header.host.clone(),
+        base_directory: header.base_directory.clone(),
+        generator: header.generator.clone(),
+        mode: header.mode.map(|mode| mode.as_str().to_string()),
+        symlink_policy: header
+            .symlink_policy
+            .map(|policy| policy.as_str().to_string()),
+        ignore_patterns: header.ignore_patterns.clone(),
+        one_file_system: header.one_file_system,
+        record_dirs: header.record_dirs,
+        record_link_groups: header.record_link_groups,
+        symlinks: Vec::new(),
+        directories: Vec::new(),
+    };
+    serde_json::to_writer(&mut writer, &header)?;
+    writeln!(writer)?;
+
+    //'/' on every platform
+    for symlink in symlinks {
+        let path = relative_path_string(&symlink.path, base_path)?;
+        #[cfg(windows)]
+        let path = path.replace('\\', "/");
+
+        let record = JsonRecord::Symlink(JsonSymlink {
+            path,
+            target: symlink.target.to_string_lossy().to_string(),
+        });
+        serde_json::to_writer(&mut writer, &record)?;
+        writeln!(writer)?;
+    }
+
+    for dir in dirs {
+        let path = relative_path_string(dir, base_path)?;
+        #[cfg(windows)]
+        let path = path.replace('\\', "/");
+
+        serde_json::to_writer(&mut writer, &JsonRecord::Dir { path })?;
+        writeln!(writer)?;
+    }
+
+    for file in snapshot.iter() {
+        let rel_path = file
+            .path
+            .strip_prefix(base_path)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        //'/' on every platform
+        let path = rel_path.to_string_lossy();
+        #[cfg(windows)]
+        let path = path.replace('\\', "/");
+
+        let hash = file
+            .calculated_hash
+            .as_ref()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "BUG: File entry missing hash value",
+                )
+            })?
+            .to_string();
+
+        let unix = file
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.unix.as_ref());
+        let changed = unix.map(|unix| {
+            unix.changed
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+        });
+
+        let entry = JsonEntry {
+            path: path.to_string(),
+            hash,
+            size: file.metadata.as_ref().map(|metadata| metadata.size),
+            mtime: file
+                .metadata
+                .as_ref()
+                .map(|metadata| metadata.last_modified_secs()),
+            mtime_nsec: file
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.last_modified_nanos()),
+            mode: unix.map(|unix| unix.mode),
+            uid: unix.map(|unix| unix.uid),
+            gid: unix.map(|unix| unix.gid),
+            ctime: changed.map(|changed| changed.as_secs()),
+            ctime_nsec: changed.map(|changed| changed.subsec_nanos()),
+            link_group: file
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.link_group),
+        };
+        serde_json::to_writer(&mut writer, &entry)?;
+        writeln!(writer)?;
+    }
+
+    //Checksum over everything above, so corruption of the digest itself is detected
+    let checksum = JsonChecksum {
+        checksum: HashSHA256::from_state(writer.state).to_string(),
+    };
+    let mut writer = writer.writer;
+    serde_json::to_writer(&mut writer, &checksum)?;
+    writeln!(writer)?;
+
+    writer.finish()
+}
+
+/// Reads a binary digest. All integers are LEB128 varints unless noted:
+///
+/// "SFDD", version (u8), algorithm name length, algorithm name,
+/// header field count, per field: key length, key, value length, value (see DigestHeader::fields)
+/// hash length, entry count
+/// per entry:
+///   shared path prefix length (bytes shared with the previous path), suffix length, suffix
+///   hash (hash length bytes)
+///   flags (u8), if BINARY_HAS_METADATA: size, last modified (seconds since the unix epoch)
+///               if BINARY_HAS_MTIME_NANOS: nanoseconds of the last modified time
+///               if BINARY_HAS_UNIX: mode, uid, gid, change time (seconds, nanoseconds)
+///               if BINARY_HAS_LINK_GROUP: link group
+/// symlink count (version 5 and newer)
+/// per symlink: path length, path, target length, target
+/// directory count (version 7 and newer)
+/// per directory: path length, path
+/// SHA-256 of every byte above (32 bytes, version 8 and newer)
+///
+/// Paths are relative UTF-8 and always use '/'. A digest whose checksum doesn't match is refused.
+fn read_binary_dd<H: HashValue, R: BufRead>(
+    mut reader: R,
+    base_path: &Path,
+) -> io::Result<DigestContents<H>> {
+    //Parsed from memory instead of with many small reads, the file is compact
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let mut reader = data.as_slice();
+
+    let (version, algorithm) = read_binary_header(&mut reader)?;
+    if !H::parse_hash_type_string(&algorithm) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported hash type '{}'", algorithm),
+        ));
+    }
+
+    if version >= 8 {
+        let body_len = reader
+            .len()
+            .checked_sub(HashSHA256::byte_len())
+            .ok_or(io::ErrorKind::UnexpectedEof)?;
+        verify_binary_checksum(&data)?;
+        reader = &reader[..body_len];
+    }
+
+    //Version 1 digests have no header fields
+    let mut header = DigestHeader::default();
+    if version >= 2 {
+        let field_count = read_varint(&mut reader)?;
+        for _ in 0..field_count {
+            let key = read_binary_string(&mut reader)?;
+            let value = read_binary_string(&mut reader)?;
+            header.set_field(&key, &value);
+        }
+    }
+
+    let hash_len = read_varint(&mut reader)? as usize;
+    if hash_len != H::byte_len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Invalid hash length {} for {}",
+                hash_len,
+                H::signature_to_string()
+            ),
+        ));
+    }
+
+    let entry_count = read_varint(&mut reader)? as usize;
+
+    //don't trust the count for the allocation, a corrupt file would allocate a lot
+    let mut files = Vec::with_capacity(entry_count.min(data.len()));
+    let mut path = String::new();
+
+    for index in 0..entry_count {
+        let invalid_entry = |what: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid {} in binary digest entry {}", what, index),
+            )
+        };
+
+        let shared = read_varint(&mut reader)? as usize;
+        let suffix_len = read_varint(&mut reader)? as usize;
+        if shared > path.len() || !path.is_char_boundary(shared) {
+            return Err(invalid_entry("path prefix"));
+        }
+        if suffix_len > reader.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let (suffix, rest) = reader.split_at(suffix_len);
+        reader = rest;
+
+        path.truncate(shared);
+        path.push_str(std::str::from_utf8(suffix).map_err(|_| invalid_entry("path"))?);
+
+        if hash_len > reader.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let (hash, rest) = reader.split_at(hash_len);
+        reader = rest;
+        let hash = H::from_bytes(hash).ok_or_else(|| invalid_entry("hash"))?;
+
+        let mut flags = [0u8; 1];
+        reader.read_exact(&mut flags)?;
+        let metadata = if flags[0] & BINARY_HAS_METADATA != 0 {
+            let size = read_varint(&mut reader)?;
+            let secs = read_varint(&mut reader)?;
+            let mut metadata = if flags[0] & BINARY_HAS_MTIME_NANOS != 0 {
+                let nanos = read_varint(&mut reader)?;
+                if nanos >= 1_000_000_000 {
+                    return Err(invalid_entry("modification time"));
+                }
+                FileMetadata::new(
+                    std::time::UNIX_EPOCH + std::time::Duration::new(secs, nanos as u32),
+                    size,
+                )
+            } else {
+                FileMetadata::new_secs(secs, size)
+            };
+
+            if flags[0] & BINARY_HAS_UNIX != 0 {
+                let mut read_u32 = || -> io::Result<u32> {
+                    u32::try_from(read_varint(&mut reader)?)
+                        .map_err(|_| invalid_entry("mode or ownership"))
+                };
+                let mode = read_u32()?;
+                let uid = read_u32()?;
+                let gid = read_u32()?;
+                let changed_secs = read_varint(&mut reader)?;
+                let changed_nanos = read_varint(&mut reader)?;
+                if changed_nanos >= 1_000_000_000 {
+                    return Err(invalid_entry("change time"));
+                }
+                metadata.unix = Some(UnixMetadata {
+                    mode,
+                    uid,
+                    gid,
+                    changed: std::time::UNIX_EPOCH
+                        + std::time::Duration::new(changed_secs, changed_nanos as u32),
+                });
+            }
+
+            if flags[0] & BINARY_HAS_LINK_GROUP != 0 {
+                metadata.link_group = Some(read_varint(&mut reader)?);
+            }
+
+            Some(metadata)
+        } else {
+            None
+        };
+
+        //Stored with '/', which only needs replacing on windows
+        #[cfg(windows)]
+        let file_path = base_path.join(normalize_separators(&path));
+        #[cfg(not(windows))]
+        let file_path = base_path.join(&path);
+
+        files.push(FileSt::new(file_path, Some(hash), metadata));
+    }
+
+    let mut symlinks = Vec::new();
+    if version >= 5 {
+        let symlink_count = read_varint(&mut reader)?;
+        for _ in 0..symlink_count {
+            let path = read_binary_string(&mut reader)?;
+            let target = read_binary_string(&mut reader)?;
+            symlinks.push(SymlinkSt::new(
+                base_path.join(normalize_separators(&path)),
+                PathBuf::from(target),
+            ));
+        }
+    }
+
+    let mut dirs = Vec::new();
+    if version >= 7 {
+        let dir_count = read_varint(&mut reader)?;
+        for _ in 0..dir_count {
+            let path = read_binary_string(&mut reader)?;
+            dirs.push(base_path.join(normalize_separators(&path)));
+        }
+    }
+
+    //anything left over means the counts or lengths above were misread
+    if !reader.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Binary digest has {} unexpected bytes after its last section",
+                reader.len()
+            ),
+        ));
+    }
+
+    if files.is_empty() && dirs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No file entries found in binary digest",
+        ));
+    }
+
+    Ok(DigestContents {
+        header,
+        files,
+        symlinks,
+        dirs,
+    })
+}
+
+//The last 32 bytes are the SHA-256 of the rest
+fn verify_binary_checksum(data: &[u8]) -> io::Result<()> {
+    let (body, expected) = data.split_at(data.len() - HashSHA256::byte_len());
+
+    let mut state = HashSHA256::new_state();
+    state.update(body);
+    let actual = HashSHA256::from_state(state);
+
+    if actual.to_bytes() != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Digest file is corrupted: its checksum is {}, but its content hashes to {}",
+                bytes_to_hex(expected),
+                actual.to_string()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+//Reads the magic, returns the version and the algorithm name
+fn read_binary_header<R: Read>(reader: &mut R) -> io::Result<(u8, String)> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != BINARY_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a binary digest file",
+        ));
+    }
+
+    let mut version = [0u8; 1];
+    reader.read_exact(&mut version)?;
+    if version[0] > BINARY_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Binary digest version {} is newer than the supported version {}",
+                version[0], BINARY_VERSION
+            ),
+        ));
+    }
+
+    Ok((version[0], read_binary_string(reader)?))
+}
+
+fn read_binary_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = read_varint(reader)?;
+    let mut string = String::new();
+    reader.take(len).read_to_string(&mut string)?;
+
+    if string.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(string)
+}
+
+fn write_binary_string<W: Write>(writer: &mut W, string: &str) -> io::Result<()> {
+    write_varint(writer, string.len() as u64)?;
+    writer.write_all(string.as_bytes())
+}
+
+fn write_binary_dd<H: HashValue>(
+    snapshot: &Vec<&FileSt<H>>,
+    symlinks: &[SymlinkSt],
+    dirs: &[PathBuf],
+    dd_file_path: &Path,
+    base_path: &Path,
+    header: &DigestHeader,
+) -> io::Result<()> {
+    let mut writer = ChecksumWriter {
+        writer: create_dd_file(dd_file_path)?,
+        state: HashSHA256::new_state(),
+    };
+
+    writer.write_all(BINARY_MAGIC)?;
+    writer.write_all(&[BINARY_VERSION])?;
+    write_binary_string(&mut writer, H::signature_to_string())?;
+
+    let fields = header.fields();
+    write_varint(&mut writer, fields.len() as u64)?;
+    for (key, value) in fields {
+        write_binary_string(&mut writer, key)?;
+        write_binary_string(&mut writer, &value)?;
+    }
+    write_varint(&mut writer, H::byte_len() as u64)?;
+    write_varint(&mut writer, snapshot.len() as u64)?;
+
+    let mut previous_path = String::new();
+
+    for file in snapshot.iter() {
+        let rel_path = file
+            .path
+            .strip_prefix(base_path)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        //'/' on every platform
+        let path = rel_path.to_string_lossy();
+        #[cfg(windows)]
+        let path = path.replace('\\', "/");
+
+        //Shared prefix with the previous path, on a char boundary
+        let shared = previous_path
+            .char_indices()
+            .zip(path.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, c), _)| i + c.len_utf8());
+        let suffix = &path[shared..];
+
+        write_varint(&mut writer, shared as u64)?;
+        write_varint(&mut writer, suffix.len() as u64)?;
+        writer.write_all(suffix.as_bytes())?;
+
+        let hash = file.calculated_hash.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "BUG: File entry missing hash value",
+            )
+        })?;
+        writer.write_all(&hash.to_bytes())?;
+
+        match &file.metadata {
+            Some(metadata) => {
+                let nanos = metadata.last_modified_nanos();
+                let mut flags = BINARY_HAS_METADATA;
+                if nanos.is_some() {
+                    flags |= BINARY_HAS_MTIME_NANOS;
+                }
+                if metadata.unix.is_some() {
+                    flags |= BINARY_HAS_UNIX;
+                }
+                if metadata.link_group.is_some() {
+                    flags |= BINARY_HAS_LINK_GROUP;
+                }
+                writer.write_all(&[flags])?;
+                write_varint(&mut writer, metadata.size)?;
+                write_varint(&mut writer, metadata.last_modified_secs())?;
+                if let Some(nanos) = nanos {
+                    write_varint(&mut writer, nanos as u64)?;
+                }
+                if let Some(unix) = &metadata.unix {
+                    let changed = unix
+                        .changed
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default();
+                    write_varint(&mut writer, unix.mode as u64)?;
+                    write_varint(&mut writer, unix.uid as u64)?;
+                    write_varint(&mut writer, unix.gid as u64)?;
+                    write_varint(&mut writer, changed.as_secs())?;
+                    write_varint(&mut writer, changed.subsec_nanos() as u64)?;
+                }
+                if let Some(link_group) = metadata.link_group {
+                    write_varint(&mut writer, link_group)?;
+                }
+            }
+            None => writer.write_all(&[0])?,
+        }
+
+        previous_path = path.to_string();
+    }
+
+    write_varint(&mut writer, symlinks.len() as u64)?;
+    for symlink in symlinks {
+        //'/' on every platform
+        let path = relative_path_string(&symlink.path, base_path)?;
+        #[cfg(windows)]
+        let path = path.replace('\\', "/");
+
+        write_binary_string(&mut writer, &path)?;
+        write_binary_string(&mut writer, &symlink.target.to_string_lossy())?;
+    }
+
+    write_varint(&mut writer, dirs.len() as u64)?;
+    for dir in dirs {
+        let path = relative_path_string(dir, base_path)?;
+        #[cfg(windows)]
+        let path = path.replace('\\', "/");
+
+        write_binary_string(&mut writer, &path)?;
+    }
+
+    //Checksum over everything above, so corruption of the digest itself is detected
+    let checksum = HashSHA256::from_state(writer.state).to_bytes();
+    let mut writer = writer.writer;
+    writer.write_all(&checksum)?;
+
+    writer.finish()
+}
+
+fn write_native_dd<H: HashValue>(
+    snapshot: &Vec<&FileSt<H>>,
+    symlinks: &[SymlinkSt],
+    dirs: &[PathBuf],
+    dd_file_path: &Path,
+    base_path: &Path,
+    header: &DigestHeader,
+) -> io::Result<()> {
+    let mut writer = ChecksumWriter {
+        writer: create_dd_file(dd_file_path)?,
+        state: HashSHA256::new_state(),
+    };
+
+    //Title
+    let generated_at = match &header.generated_at {
+        Some(generated_at) => generated_at.clone(),
+        None => chrono::Local::now().to_rfc3339(),
+    };
+    writeln!(
+        writer,
+        "{} {}{} containing {} entries",
+        DD_COMMENT_CHAR,
+        NATIVE_TITLE_PREFIX,
+        generated_at,
+        snapshot.len()
+    )?;
+
+    writeln!(
+        writer,
+        "{} Format: {}",
+        DD_COMMENT_CHAR, NATIVE_FORMAT_VERSION
+    )?;
+
+    //Entries without metadata (eg. from a md5sum file) are written without a metadata line
+    let capabilities: Vec<&str> = NATIVE_CAPABILITIES
+        .iter()
+        .copied()
+        .filter(|&capability| {
+            capability != CAPABILITY_METADATA || snapshot.iter().all(|file| file.metadata.is_some())
+        })
+        .collect();
+    writeln!(
+        writer,
+        "{} Capabilities: {}",
+        DD_COMMENT_CHAR,
+        capabilities.join(", ")
+    )?;
+
+    //The time is already in the title. A value with a line break (eg. an odd directory name)
+    //would end the comment, so it is left out
+    for (key, value) in header.fields() {
+        if key != HEADER_GENERATED_AT && !value.contains(['\n', '\r']) {
+            writeln!(writer, "{} {}: {}", DD_COMMENT_CHAR, key, value)?;
+        }
+    }
+
+    //Hash signature
+    writeln!(
+        writer,
+        "{} Hash: {}",
+        DD_COMMENT_CHAR,
+        H::signature_to_string()
+    )?;
+
+    if !H::is_cryptographic() {
+        writeln!(
+            writer,
+            "{} Note: {} is not a cryptographic hash. This digest detects accidental corruption, but it is NOT tamper-evident",
+            DD_COMMENT_CHAR,
+            H::signature_to_string()
+        )?;
+    }
+
+    for file in snapshot.iter() {
+        //Metadata comment
+        if let Some(metadata) = &file.metadata {
+            writeln!(writer, "{} {}", DD_COMMENT_CHAR, metadata)?;
+        }
+
+        //Split path into relative path
+        let rel_path = file
+            .path
+            .strip_prefix(base_path)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let path_str = rel_path.to_string_lossy();
+
+        //Get hash
+        let hash_str = file
+            .calculated_hash
+            .as_ref()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "BUG: File entry missing hash value",
+                )
+            })?
+            .to_string();
+
+        writeln!(writer, "{} *{}", hash_str, path_str)?;
+    }
+
+    for symlink in symlinks {
+        let path = relative_path_string(&symlink.path, base_path)?;
+        let target = symlink.target.to_string_lossy();
+        writeln!(
+            writer,
+            "{} {}{} -> {}",
+            DD_COMMENT_CHAR,
+            SYMLINK_PREFIX,
+            serde_json::to_string(&path)?,
+            serde_json::to_string(&target)?
+        )?;
+    }
+
+    for dir in dirs {
+        let path = relative_path_string(dir, base_path)?;
+        writeln!(
+            writer,
+            "{} {}{}",
+            DD_COMMENT_CHAR,
+            DIRECTORY_PREFIX,
+            serde_json::to_string(&path)?
+        )?;
+    }
+
+    //Checksum over everything above, so corruption of the digest itself is detected
+    let checksum = HashSHA256::from_state(writer.state).to_string();
+    let mut writer = writer.writer;
+    writeln!(
+        writer,
+        "{} {}{}",
+        DD_COMMENT_CHAR, CHECKSUM_TRAILER_PREFIX, checksum
+    )?;
+
+    writer.finish()
+}
+
+pub fn parse_dd_hash_type(dd_file_path: &Path) -> Option<HashAlgorithm> {
+    let mut file = open_dd_file(dd_file_path).ok()?;
+
+    if DigestFormat::from_path(dd_file_path) == DigestFormat::Binary {
+        let (_, algorithm) = read_binary_header(&mut file).ok()?;
+        return hash_algorithm_by_name(algorithm);
+    }
+
+    let mut lines = file.lines();
+
+    //JSON digests name the algorithm in the header line
+    if DigestFormat::from_path(dd_file_path) == DigestFormat::Json {
+        let header: JsonHeader = serde_json::from_str(&lines.next()?.ok()?).ok()?;
+        return hash_algorithm_by_name(header.algorithm);
+    }
+
+    //Skip all lines until 'C Hash: <hash type>'
+    while let Some(Ok(line)) = lines.next() {
+        if line.starts_with(&format!("{} ", DD_COMMENT_CHAR)) {
+            //Get the line, and remove the comment and space
+            if let Some(hash_str) = line.strip_prefix(&format!("{} ", DD_COMMENT_CHAR)) {
+                if hash_str.starts_with("Hash: ") {
+                    // Extract the hash type
+                    if let Some(hash_type_str) = hash_str.strip_prefix("Hash: ") {
+                        return hash_algorithm_by_name(hash_type_str);
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_rep::hash::crc32::HashCRC32;
+    use crate::file_rep::hash::md5::HashMD5;
+    use std::fs;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const BASE: &str = "/base";
+
+    //an empty directory of its own for each test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sfisum-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn file<H: HashValue>(path: &str, hash: &str, metadata: Option<FileMetadata>) -> FileSt<H> {
+        let mut file = FileSt::new(Path::new(BASE).join(path), None, metadata);
+        file.calculated_hash = H::new_from_string(hash);
+        file
+    }
+
+    //every kind of entry: all metadata flags, whole seconds, and none at all
+    fn sample_files() -> Vec<FileSt<HashMD5>> {
+        let mut full =
+            FileMetadata::new(UNIX_EPOCH + Duration::new(1733589895, 123456789), 2999880);
+        full.unix = Some(UnixMetadata {
+            mode: 0o100644,
+            uid: 1000,
+            gid: 100,
+            changed: UNIX_EPOCH + Duration::new(1733589896, 5),
+        });
+        full.link_group = Some(1);
+
+        vec![
+            file(
+                "a.txt",
+                "4534bfadb395bc299157d52eac16c368",
+                Some(full.clone()),
+            ),
+            file(
+                "dir/a b é.txt",
+                "d41d8cd98f00b204e9800998ecf8427e",
+                Some(full),
+            ),
+            file(
+                "dir/old.txt",
+                "0123456789abcdef0123456789abcdef",
+                Some(FileMetadata::new_secs(1733589000, 0)),
+            ),
+            file("dir/unknown.txt", "fedcba9876543210fedcba9876543210", None),
+        ]
+    }
+
+    fn sample_symlinks() -> Vec<SymlinkSt> {
+        vec![SymlinkSt::new(
+            Path::new(BASE).join("dir/link \"x\""),
+            PathBuf::from("../a.txt"),
+        )]
+    }
+
+    fn sample_dirs() -> Vec<PathBuf> {
+        vec![
+            Path::new(BASE).join("dir"),
+            Path::new(BASE).join("dir/empty"),
+        ]
+    }
+
+    fn sample_header() -> DigestHeader {
+        DigestHeader {
+            generated_at: Some("2024-12-07T17:44:55+01:00".to_string()),
+            host: Some("host".to_string()),
+            base_directory: Some(BASE.to_string()),
+            generator: Some("sfisum 0.1.0".to_string()),
+            mode: Some(DigestMode::FastRefresh),
+            symlink_policy: Some(SymlinkPolicy::Record),
+            ignore_patterns: vec!["*.tmp".to_string(), "!keep.tmp".to_string()],
+            one_file_system: Some(true),
+            record_dirs: Some(true),
+            record_link_groups: Some(true),
+        }
+    }
+
+    fn write_sample(dd_file_path: &Path) {
+        let files = sample_files();
+        write_dd(
+            &files.iter().collect(),
+            &sample_symlinks(),
+            &sample_dirs(),
+            dd_file_path,
+            Path::new(BASE),
+            &sample_header(),
+            None,
+        )
+        .unwrap();
+    }
+
+    //path, hash and metadata line of each entry, which covers every stored field
+    fn entries<H: HashValue>(files: &[FileSt<H>]) -> Vec<(PathBuf, String, Option<String>)> {
+        files
+            .iter()
+            .map(|file| {
+                let hash = file.loaded_hash.as_ref().or(file.calculated_hash.as_ref());
+                (
+                    file.path.clone(),
+                    hash.unwrap().to_string(),
+                    file.metadata.as_ref().map(|metadata| metadata.to_string()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = test_dir("round-trip");
+
+        for name in [
+            "digest.ddmd5",
+            "digest.ddmd5.jsonl",
+            "digest.ddmd5.bin",
+            "digest.ddmd5.zst",
+            "digest.ddmd5.jsonl.gz",
+            "digest.ddmd5.bin.zst",
+        ] {
+            let dd_file_path = dir.join(name);
+            write_sample(&dd_file_path);
+
+            let contents = read_dd::<HashMD5>(&dd_file_path, Path::new(BASE)).unwrap();
+            assert_eq!(
+                entries(&contents.files),
+                entries(&sample_files()),
+                "{}",
+                name
+            );
+            assert_eq!(contents.symlinks, sample_symlinks(), "{}", name);
+            assert_eq!(contents.dirs, sample_dirs(), "{}", name);
+            assert_eq!(
+                contents.header.fields(),
+                sample_header().fields(),
+                "{}",
+                name
+            );
+
+            let data = fs::read(&dd_file_path).unwrap();
+            let contents = read_dd_data::<HashMD5>(&dd_file_path, &data, Path::new(BASE)).unwrap();
+            assert_eq!(
+                entries(&contents.files),
+                entries(&sample_files()),
+                "{}",
+                name
+            );
+
+            assert_eq!(
+                parse_dd_hash_type(&dd_file_path).map(|a| a.name),
+                Some("md5"),
+                "{}",
+                name
+            );
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    //a binary digest of the first version: no header fields, symlinks or directories
+    #[test]
+    fn binary_version_1() {
+        let dir = test_dir("binary-v1");
+        let dd_file_path = dir.join("digest.ddmd5.bin");
+
+        let mut data = Vec::new();
+        data.extend(BINARY_MAGIC);
+        data.push(1);
+        write_binary_string(&mut data, "md5").unwrap();
+        write_varint(&mut data, 16).unwrap();
+        write_varint(&mut data, 2).unwrap();
+        for (shared, suffix, hash) in [
+            (0, "dir/a.txt", "4534bfadb395bc299157d52eac16c368"),
+            (4, "b.txt", "d41d8cd98f00b204e9800998ecf8427e"),
+        ] {
+            write_varint(&mut data, shared).unwrap();
+            write_binary_string(&mut data, suffix).unwrap();
+            data.extend(HashMD5::new_from_string(hash).unwrap().to_bytes());
+            data.push(BINARY_HAS_METADATA);
+            write_varint(&mut data, 10).unwrap();
+            write_varint(&mut data, 1733589895).unwrap();
+        }
+        fs::write(&dd_file_path, data).unwrap();
+
+        let contents = read_dd::<HashMD5>(&dd_file_path, Path::new(BASE)).unwrap();
+        let metadata = Some("Size: 10, Last modified: 1733589895".to_string());
+        assert_eq!(
+            entries(&contents.files),
+            [
+                (
+                    Path::new(BASE).join("dir/a.txt"),
+                    "4534bfadb395bc299157d52eac16c368".to_string(),
+                    metadata.clone()
+                ),
+                (
+                    Path::new(BASE).join("dir/b.txt"),
+                    "d41d8cd98f00b204e9800998ecf8427e".to_string(),
+                    metadata
+                ),
+            ]
+        );
+        assert!(contents.header.is_empty());
+        assert!(contents.symlinks.is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn binary_leftover_bytes() {
+        let dir = test_dir("binary-leftover");
+        let dd_file_path = dir.join("digest.ddmd5.bin");
+        write_sample(&dd_file_path);
+
+        //a valid checksum over a body with a byte too many
+        let mut data = fs::read(&dd_file_path).unwrap();
+        data.truncate(data.len() - HashSHA256::byte_len());
+        data.push(0);
+        let mut state = HashSHA256::new_state();
+        state.update(&data);
+        data.extend(HashSHA256::from_state(state).to_bytes());
+        fs::write(&dd_file_path, data).unwrap();
+
+        let error = read_dd::<HashMD5>(&dd_file_path, Path::new(BASE))
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("1 unexpected bytes"), "{}", error);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn binary_newer_version() {
+        let dir = test_dir("binary-newer");
+        let dd_file_path = dir.join("digest.ddmd5.bin");
+
+        let mut data = Vec::new();
+        data.extend(BINARY_MAGIC);
+        data.push(BINARY_VERSION + 1);
+        write_binary_string(&mut data, "md5").unwrap();
+        fs::write(&dd_file_path, data).unwrap();
+
+        let error = read_dd::<HashMD5>(&dd_file_path, Path::new(BASE))
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("newer"), "{}", error);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn native_checksum_trailer() {
+        let dir = test_dir("native-checksum");
+        let dd_file_path = dir.join("digest.ddmd5");
+        write_sample(&dd_file_path);
+        let content = fs::read_to_string(&dd_file_path).unwrap();
+        assert!(content
+            .lines()
+            .last()
+            .unwrap()
+            .starts_with(&format!("{} {}", DD_COMMENT_CHAR, CHECKSUM_TRAILER_PREFIX)));
+
+        let read_error = |content: &str| {
+            fs::write(&dd_file_path, content).unwrap();
+            read_dd::<HashMD5>(&dd_file_path, Path::new(BASE))
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        let error = read_error(&content.replace("a.txt", "b.txt"));
+        assert!(error.contains("content hashes to"), "{}", error);
+
+        let (truncated, _) = content.trim_end().rsplit_once('\n').unwrap();
+        let error = read_error(&format!("{}\n", truncated));
+        assert!(error.contains("checksum line is missing"), "{}", error);
+
+        let error = read_error(&format!("{}extra\n", content));
+        assert!(
+            error.contains("content after its checksum line"),
+            "{}",
+            error
+        );
+
+        //trailing empty lines are fine
+        fs::write(&dd_file_path, format!("{}\n\n", content)).unwrap();
+        assert!(read_dd::<HashMD5>(&dd_file_path, Path::new(BASE)).is_ok());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn json_checksum_trailer() {
+        let dir = test_dir("json-checksum");
+        let dd_file_path = dir.join("digest.ddmd5.jsonl");
+        write_sample(&dd_file_path);
+        let content = fs::read_to_string(&dd_file_path).unwrap();
+        assert!(content
+            .lines()
+            .last()
+            .unwrap()
+            .starts_with("{\"checksum\":"));
+
+        let read_error = |content: &str| {
+            fs::write(&dd_file_path, content).unwrap();
+            read_dd::<HashMD5>(&dd_file_path, Path::new(BASE))
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        let error = read_error(&content.replace("a.txt", "b.txt"));
+        assert!(error.contains("content hashes to"), "{}", error);
+
+        let (truncated, _) = content.trim_end().rsplit_once('\n').unwrap();
+        let error = read_error(&format!("{}\n", truncated));
+        assert!(error.contains("checksum line is missing"), "{}", error);
+
+        //version 1 digests have no checksum
+        let version_1 = truncated.replace(
+            &format!("\"version\":{}", JSON_SCHEMA_VERSION),
+            "\"version\":1",
+        );
+        fs::write(&dd_file_path, version_1).unwrap();
+        assert!(read_dd::<HashMD5>(&dd_file_path, Path::new(BASE)).is_ok());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    //symlinks and directories in the header, no checksum
+    #[test]
+    fn json_version_1() {
+        let dir = test_dir("json-v1");
+        let dd_file_path = dir.join("digest.ddmd5.jsonl");
+        fs::write(
+            &dd_file_path,
+            "{\"schema\":\"sfisum-digest\",\"version\":1,\"algorithm\":\"md5\",\
+             \"symlinks\":[{\"path\":\"dir/link\",\"target\":\"../a.txt\"}],\
+             \"directories\":[\"dir/empty\"]}\n\
+             {\"path\":\"dir/a.txt\",\"hash\":\"4534bfadb395bc299157d52eac16c368\",\
+             \"size\":10,\"mtime\":1733589895}\n",
+        )
+        .unwrap();
+
+        let contents = read_dd::<HashMD5>(&dd_file_path, Path::new(BASE)).unwrap();
+        assert_eq!(
+            entries(&contents.files),
+            [(
+                Path::new(BASE).join("dir/a.txt"),
+                "4534bfadb395bc299157d52eac16c368".to_string(),
+                Some("Size: 10, Last modified: 1733589895".to_string())
+            )]
+        );
+        assert_eq!(
+            contents.symlinks,
+            [SymlinkSt::new(
+                Path::new(BASE).join("dir/link"),
+                PathBuf::from("../a.txt")
+            )]
+        );
+        assert_eq!(contents.dirs, [Path::new(BASE).join("dir/empty")]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn binary_checksum() {
+        let dir = test_dir("binary-checksum");
+        let dd_file_path = dir.join("digest.ddmd5.bin");
+        write_sample(&dd_file_path);
+        let data = fs::read(&dd_file_path).unwrap();
+
+        let read_error = |data: &[u8]| {
+            fs::write(&dd_file_path, data).unwrap();
+            read_dd::<HashMD5>(&dd_file_path, Path::new(BASE))
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        let mut corrupted = data.clone();
+        let middle = corrupted.len() / 2;
+        corrupted[middle] ^= 1;
+        let error = read_error(&corrupted);
+        assert!(error.contains("content hashes to"), "{}", error);
+
+        let error = read_error(&data[..data.len() - 1]);
+        assert!(error.contains("content hashes to"), "{}", error);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    //written before the format and checksum lines existed
+    #[test]
+    fn native_format_0() {
+        let dir = test_dir("native-format-0");
+        let dd_file_path = dir.join("digest.ddmd5");
+        fs::write(
+            &dd_file_path,
+            "; Directory digest generated at 2024-12-07 containing 1 entries\n\
+             ; Hash: md5\n\
+             ; Size: 10, Last modified: 1733589895\n\
+             4534bfadb395bc299157d52eac16c368 *dir\\a.txt\n",
+        )
+        .unwrap();
+
+        let contents = read_dd::<HashMD5>(&dd_file_path, Path::new(BASE)).unwrap();
+        assert_eq!(
+            entries(&contents.files),
+            [(
+                Path::new(BASE).join("dir").join("a.txt"),
+                "4534bfadb395bc299157d52eac16c368".to_string(),
+                Some("Size: 10, Last modified: 1733589895".to_string())
+            )]
+        );
+        assert_eq!(contents.header.generated_at.as_deref(), Some("2024-12-07"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn coreutils_round_trip() {
+        let dir = test_dir("coreutils");
+        let dd_file_path = dir.join("digest.md5");
+
+        let files: Vec<FileSt<HashMD5>> = vec![
+            file("a b.txt", "4534bfadb395bc299157d52eac16c368", None),
+            file("new\nline", "d41d8cd98f00b204e9800998ecf8427e", None),
+        ];
+        write_dd(
+            &files.iter().collect(),
+            &[],
+            &[],
+            &dd_file_path,
+            Path::new(BASE),
+            &sample_header(),
+            None,
+        )
+        .unwrap();
+
+        let content = fs::read_to_string(&dd_file_path).unwrap();
+        assert_eq!(
+            content,
+            "4534bfadb395bc299157d52eac16c368 *a b.txt\n\\d41d8cd98f00b204e9800998ecf8427e *new\\nline\n"
+        );
+
+        let contents = read_dd::<HashMD5>(&dd_file_path, Path::new(BASE)).unwrap();
+        assert_eq!(entries(&contents.files), entries(&files));
+        assert!(contents.header.is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn coreutils_unescape() {
+        assert_eq!(
+            unescape_coreutils_path("a\\\\b\\nc\\rd").as_deref(),
+            Some("a\\b\nc\rd")
+        );
+        assert_eq!(unescape_coreutils_path("plain").as_deref(), Some("plain"));
+        assert_eq!(unescape_coreutils_path("a\\tb"), None);
+        assert_eq!(unescape_coreutils_path("trailing\\"), None);
+    }
+
+    #[test]
+    fn sfv() {
+        let dir = test_dir("sfv");
+        let dd_file_path = dir.join("digest.sfv");
+        fs::write(
+            &dd_file_path,
+            "; Generated by some tool\n\
+             \n\
+             dir\\a file.txt   1A2B3C4D\n\
+             b.txt 00ff00ff \n",
+        )
+        .unwrap();
+
+        let contents = read_dd::<HashCRC32>(&dd_file_path, Path::new(BASE)).unwrap();
+        assert_eq!(
+            entries(&contents.files),
+            [
+                (
+                    Path::new(BASE).join("dir").join("a file.txt"),
+                    "1a2b3c4d".to_string(),
+                    None
+                ),
+                (Path::new(BASE).join("b.txt"), "00ff00ff".to_string(), None),
+            ]
+        );
+
+        //written back with upper case checksums, and read the same
+        let files: Vec<FileSt<HashCRC32>> = vec![file("a b.txt", "1a2b3c4d", None)];
+        write_dd(
+            &files.iter().collect(),
+            &[],
+            &[],
+            &dd_file_path,
+            Path::new(BASE),
+            &DigestHeader::default(),
+            None,
+        )
+        .unwrap();
+        let content = fs::read_to_string(&dd_file_path).unwrap();
+        assert!(content.ends_with("\na b.txt 1A2B3C4D\n"), "{}", content);
+        let contents = read_dd::<HashCRC32>(&dd_file_path, Path::new(BASE)).unwrap();
+        assert_eq!(entries(&contents.files), entries(&files));
+
+        fs::write(&dd_file_path, "a.txt\n").unwrap();
+        assert!(read_dd::<HashCRC32>(&dd_file_path, Path::new(BASE)).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,208 @@
+use crate::file_rep::directory_walker::SymlinkPolicy;
+use std::fmt;
+use std::path::Path;
+
+/// The operation that produced a digest
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DigestMode {
+    Generate,
+    Validate,
+    FastRefresh,
+    FullRefresh,
+    Migrate,
+}
+
+impl DigestMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DigestMode::Generate => "generate",
+            DigestMode::Validate => "validate",
+            DigestMode::FastRefresh => "fast refresh",
+            DigestMode::FullRefresh => "full refresh",
+            DigestMode::Migrate => "migrate",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "generate" => Some(DigestMode::Generate),
+            "validate" => Some(DigestMode::Validate),
+            "fast refresh" => Some(DigestMode::FastRefresh),
+            "full refresh" => Some(DigestMode::FullRefresh),
+            "migrate" => Some(DigestMode::Migrate),
+            _ => None,
+        }
+    }
+}
+
+pub const HEADER_GENERATED_AT: &str = "Generated at";
+pub const HEADER_HOST: &str = "Host";
+pub const HEADER_BASE_DIRECTORY: &str = "Base directory";
+pub const HEADER_GENERATOR: &str = "Generator";
+pub const HEADER_MODE: &str = "Mode";
+pub const HEADER_SYMLINKS: &str = "Symlinks";
+//once per pattern
+pub const HEADER_IGNORE: &str = "Ignore";
+pub const HEADER_ONE_FILESYSTEM: &str = "One filesystem";
+pub const HEADER_DIRECTORIES: &str = "Directories";
+pub const HEADER_LINK_GROUPS: &str = "Link groups";
+
+/// Where, when and how a digest was made. Every field is optional - digests of older versions
+/// and checksum files (md5sum, SFV) record none of them.
+#[derive(Clone, Default, Debug)]
+pub struct DigestHeader {
+    /// RFC 3339 time
+    pub generated_at: Option<String>,
+    pub host: Option<String>,
+    pub base_directory: Option<String>,
+    /// 'sfisum <version>'
+    pub generator: Option<String>,
+    pub mode: Option<DigestMode>,
+    /// How symbolic links were walked
+    pub symlink_policy: Option<SymlinkPolicy>,
+    /// Patterns of the files left out, besides the ones of the .sfisumignore files
+    pub ignore_patterns: Vec<String>,
+    /// Whether other filesystems mounted below the base directory were left out
+    pub one_file_system: Option<bool>,
+    /// Whether directories were recorded as entries, so empty ones are kept
+    pub record_dirs: Option<bool>,
+    /// Whether files that are hardlinks of each other were recorded as link groups
+    pub record_link_groups: Option<bool>,
+}
+
+impl DigestHeader {
+    /// Header of a digest made now, on this host
+    pub fn new(
+        base_path: &Path,
+        mode: DigestMode,
+        symlink_policy: SymlinkPolicy,
+        ignore_patterns: Vec<String>,
+        one_file_system: bool,
+        record_dirs: bool,
+        record_link_groups: bool,
+    ) -> Self {
+        //A relative path says little about where the digest came from
+        let base_path = base_path
+            .canonicalize()
+            .unwrap_or_else(|_| base_path.to_path_buf());
+
+        DigestHeader {
+            generated_at: Some(chrono::Local::now().to_rfc3339()),
+            host: gethostname::gethostname().into_string().ok(),
+            base_directory: Some(base_path.to_string_lossy().to_string()),
+            generator: Some(format!("sfisum {}", env!("CARGO_PKG_VERSION"))),
+            mode: Some(mode),
+            symlink_policy: Some(symlink_policy),
+            ignore_patterns,
+            one_file_system: Some(one_file_system),
+            record_dirs: Some(record_dirs),
+            record_link_groups: Some(record_link_groups),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.generated_at.is_none()
+            && self.host.is_none()
+            && self.base_directory.is_none()
+            && self.generator.is_none()
+            && self.mode.is_none()
+            && self.symlink_policy.is_none()
+            && self.ignore_patterns.is_empty()
+            && self.one_file_system.is_none()
+            && self.record_dirs.is_none()
+            && self.record_link_groups.is_none()
+    }
+
+    /// The fields as 'Key', 'value' pairs, as written to the digest
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = Vec::new();
+
+        if let Some(generated_at) = &self.generated_at {
+            fields.push((HEADER_GENERATED_AT, generated_at.clone()));
+        }
+        if let Some(host) = &self.host {
+            fields.push((HEADER_HOST, host.clone()));
+        }
+        if let Some(base_directory) = &self.base_directory {
+            fields.push((HEADER_BASE_DIRECTORY, base_directory.clone()));
+        }
+        if let Some(generator) = &self.generator {
+            fields.push((HEADER_GENERATOR, generator.clone()));
+        }
+        if let Some(mode) = &self.mode {
+            fields.push((HEADER_MODE, mode.as_str().to_string()));
+        }
+        if let Some(symlink_policy) = &self.symlink_policy {
+            fields.push((HEADER_SYMLINKS, symlink_policy.as_str().to_string()));
+        }
+        for pattern in self.ignore_patterns.iter() {
+            fields.push((HEADER_IGNORE, pattern.clone()));
+        }
+        if let Some(one_file_system) = self.one_file_system {
+            fields.push((HEADER_ONE_FILESYSTEM, yes_no(one_file_system)));
+        }
+        if let Some(record_dirs) = self.record_dirs {
+            fields.push((HEADER_DIRECTORIES, yes_no(record_dirs)));
+        }
+        if let Some(record_link_groups) = self.record_link_groups {
+            fields.push((HEADER_LINK_GROUPS, yes_no(record_link_groups)));
+        }
+
+        fields
+    }
+
+    /// Sets a field by its key, keys this version doesn't know are ignored
+    pub fn set_field(&mut self, key: &str, value: &str) {
+        let value = value.to_string();
+        match key {
+            HEADER_GENERATED_AT => self.generated_at = Some(value),
+            HEADER_HOST => self.host = Some(value),
+            HEADER_BASE_DIRECTORY => self.base_directory = Some(value),
+            HEADER_GENERATOR => self.generator = Some(value),
+            //modes of newer versions are left unknown
+            HEADER_MODE => self.mode = DigestMode::parse(&value),
+            HEADER_SYMLINKS => self.symlink_policy = SymlinkPolicy::parse(&value),
+            HEADER_IGNORE => self.ignore_patterns.push(value),
+            HEADER_ONE_FILESYSTEM => self.one_file_system = parse_yes_no(&value),
+            HEADER_DIRECTORIES => self.record_dirs = parse_yes_no(&value),
+            HEADER_LINK_GROUPS => self.record_link_groups = parse_yes_no(&value),
+            _ => {}
+        }
+    }
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+fn parse_yes_no(value: &str) -> Option<bool> {
+    match value {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// 'made on host X from /mnt/a by fast refresh at <time> (sfisum 0.1.0)'
+impl fmt::Display for DigestHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "made")?;
+        if let Some(host) = &self.host {
+            write!(f, " on host {}", host)?;
+        }
+        if let Some(base_directory) = &self.base_directory {
+            write!(f, " from {}", base_directory)?;
+        }
+        if let Some(mode) = &self.mode {
+            write!(f, " by {}", mode.as_str())?;
+        }
+        if let Some(generated_at) = &self.generated_at {
+            write!(f, " at {}", generated_at)?;
+        }
+        if let Some(generator) = &self.generator {
+            write!(f, " ({})", generator)?;
+        }
+
+        Ok(())
+    }
+}
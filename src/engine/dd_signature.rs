@@ -0,0 +1,153 @@
+use crate::constants::DD_COMMENT_CHAR;
+use crate::file_rep::hash::hex::{bytes_to_hex, hex_to_bytes};
+use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Appended to the digest file name, eg. 'x.ddmd5.sig'
+pub const SIGNATURE_SUFFIX: &str = "sig";
+
+/// What validation does with the detached signature of a digest
+#[derive(Clone, Default)]
+pub enum SignaturePolicy {
+    /// Signatures are not checked
+    #[default]
+    Ignore,
+    /// A signature, if present, must be a valid signature of the trusted key
+    VerifyIfSigned(VerifyingKey),
+    /// The digest must carry a valid signature of the trusted key
+    Require(VerifyingKey),
+}
+
+pub fn signature_path(dd_file_path: &Path) -> PathBuf {
+    let mut path = dd_file_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(SIGNATURE_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Loads a PKCS#8 PEM private key, as written by generate_key_pair or
+/// 'openssl genpkey -algorithm ed25519'
+pub fn load_signing_key(key_path: &Path) -> Result<SigningKey, String> {
+    let pem = fs::read_to_string(key_path)
+        .map_err(|e| format!("Failed to read key file {}: {}", key_path.display(), e))?;
+
+    SigningKey::from_pkcs8_pem(&pem)
+        .map_err(|e| format!("Invalid Ed25519 private key {}: {}", key_path.display(), e))
+}
+
+/// Loads a PEM public key. A private key file is accepted as well.
+pub fn load_verifying_key(key_path: &Path) -> Result<VerifyingKey, String> {
+    let pem = fs::read_to_string(key_path)
+        .map_err(|e| format!("Failed to read key file {}: {}", key_path.display(), e))?;
+
+    VerifyingKey::from_public_key_pem(&pem)
+        .or_else(|_| SigningKey::from_pkcs8_pem(&pem).map(|key| key.verifying_key()))
+        .map_err(|e| format!("Invalid Ed25519 public key {}: {}", key_path.display(), e))
+}
+
+/// Writes a new key pair as '<path>' (private) and '<path>.pub' (public)
+pub fn generate_key_pair(private_key_path: &Path) -> Result<PathBuf, String> {
+    let key = SigningKey::generate(&mut rand_core::OsRng);
+
+    let private_pem = key
+        .to_pkcs8_pem(LineEnding::LF)
+        .map_err(|e| format!("Failed to encode private key: {}", e))?;
+    let public_pem = key
+        .verifying_key()
+        .to_public_key_pem(LineEnding::LF)
+        .map_err(|e| format!("Failed to encode public key: {}", e))?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    //the private key is only readable by the owner
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options
+        .open(private_key_path)
+        .and_then(|mut file| file.write_all(private_pem.as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {}", private_key_path.display(), e))?;
+
+    let mut public_key_path = private_key_path.as_os_str().to_owned();
+    public_key_path.push(".pub");
+    let public_key_path = PathBuf::from(public_key_path);
+
+    fs::write(&public_key_path, public_pem)
+        .map_err(|e| format!("Failed to write {}: {}", public_key_path.display(), e))?;
+
+    Ok(public_key_path)
+}
+
+/// Signs the digest file as it is stored on disk, into a detached '<digest>.sig':
+///
+/// C Ed25519 signature of <digest file name>
+/// C Public key: <hex>
+/// <signature hex>
+pub fn sign_dd_file(dd_file_path: &Path, key: &SigningKey) -> io::Result<()> {
+    let data = fs::read(dd_file_path)?;
+    let signature = key.sign(&data);
+
+    let file_name = dd_file_path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+
+    let content = format!(
+        "{} Ed25519 signature of {}\n{} Public key: {}\n{}\n",
+        DD_COMMENT_CHAR,
+        file_name,
+        DD_COMMENT_CHAR,
+        bytes_to_hex(key.verifying_key().as_bytes()),
+        bytes_to_hex(&signature.to_bytes())
+    );
+
+    fs::write(signature_path(dd_file_path), content)
+}
+
+/// Checks the detached signature of a digest file against the policy. data is the content of the
+/// digest file, read by the caller so it parses the same bytes that were checked.
+pub fn verify_dd_file(
+    dd_file_path: &Path,
+    data: &[u8],
+    policy: &SignaturePolicy,
+) -> Result<(), String> {
+    let (trusted_key, required) = match policy {
+        SignaturePolicy::Ignore => return Ok(()),
+        SignaturePolicy::VerifyIfSigned(key) => (key, false),
+        SignaturePolicy::Require(key) => (key, true),
+    };
+
+    let sig_path = signature_path(dd_file_path);
+    let content = match fs::read_to_string(&sig_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return if required {
+                Err(format!(
+                    "The digest file is not signed ({} is missing), but a signature is required",
+                    sig_path.display()
+                ))
+            } else {
+                Ok(())
+            };
+        }
+        Err(e) => return Err(format!("Failed to read {}: {}", sig_path.display(), e)),
+    };
+
+    let signature = content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with(DD_COMMENT_CHAR))
+        .and_then(hex_to_bytes::<64>)
+        .map(|bytes| Signature::from_bytes(&bytes))
+        .ok_or_else(|| format!("Invalid signature file {}", sig_path.display()))?;
+
+    trusted_key.verify(data, &signature).map_err(|_| {
+        "The digest file signature is not valid for the trusted key - the digest was modified after signing, or signed with another key"
+            .to_string()
+    })
+}
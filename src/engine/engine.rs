@@ -1,32 +1,86 @@
-use crate::constants::{LARGE_FILE_THREADS, SMALL_FILE_SIZE_THRESHOLD, SMALL_FILE_THREADS};
-use crate::engine::dd_file_rw::{read_dd, write_dd};
-use crate::engine::hash_calc_planner::calculate_hashes;
-use crate::file_rep::directory_walker::DirectoryWalker;
-use crate::file_rep::file_st::FileSt;
-use crate::file_rep::hash_def::{hash_type_to_suffix, HashType, HashValue};
+use crate::constants::{
+    FILE_BUFFER_SIZE, LARGE_FILE_BUFFER_SIZE, LARGE_FILE_THREADS, SMALL_FILE_SIZE_THRESHOLD,
+    SMALL_FILE_THREADS,
+};
+use crate::engine::dd_compression::Compression;
+use crate::engine::dd_file_rw::{
+    read_dd, read_dd_data, write_dd, DigestContents, DigestFormat, BINARY_SUFFIX, JSON_SUFFIX,
+    SFV_SUFFIX,
+};
+use crate::engine::dd_header::{DigestHeader, DigestMode};
+use crate::engine::dd_signature::{verify_dd_file, SignaturePolicy};
+use crate::engine::engine_factory::HashAlgorithm;
+use crate::engine::hash_calc_planner::{calculate_hashes, HashJob};
+use crate::file_rep::directory_walker::{DirectoryWalker, SkippedEntry, SymlinkPolicy, WalkError};
+use crate::file_rep::file_hasher::read_file_chunks;
+use crate::file_rep::file_metadata::{FileId, FileMetadata, UnixMetadata};
+use crate::file_rep::file_st::{FileSt, SymlinkSt};
+use crate::file_rep::hash_def::{AnyHashState, HashState, HashValue};
+use crate::file_rep::path_filter::PathFilter;
 use crate::util::console_text_formatter::{colorize_txt, TextColor};
+use ed25519_dalek::SigningKey;
 use std::cmp::PartialEq;
 use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
-use std::time::UNIX_EPOCH;
 
 pub trait EngineAny {
-    fn new(hash_type: HashType) -> Self
+    fn new() -> Self
     where
         Self: Sized;
 
     fn set_paths(&mut self, dd_file_path: PathBuf, dir_path: PathBuf);
-    fn save_dd_file(&self, new_dd_file_dir: Option<PathBuf>) -> Result<(), String>;
+    fn set_signing_key(&mut self, signing_key: Option<SigningKey>);
+    fn set_signature_policy(&mut self, policy: SignaturePolicy);
+    /// Whether a generated digest records the mode, ownership and change time of files (unix only)
+    fn set_record_unix_metadata(&mut self, record: bool);
+    /// How a generated digest walks symbolic links, refreshes use the policy of the loaded digest
+    fn set_symlink_policy(&mut self, policy: SymlinkPolicy);
+    /// Gitignore-style patterns of files to leave out, refreshes add them to the ones of the digest
+    fn set_ignore_patterns(&mut self, patterns: Vec<String>);
+    /// Whether a generated digest leaves out other filesystems mounted below the base directory,
+    /// refreshes use the choice of the loaded digest
+    fn set_one_file_system(&mut self, one_file_system: bool);
+    /// Whether a generated digest records the directories, so empty ones are kept, refreshes use
+    /// the choice of the loaded digest
+    fn set_record_dirs(&mut self, record_dirs: bool);
+    /// Whether a generated digest records which files are hardlinks of each other (unix only),
+    /// refreshes use the choice of the loaded digest
+    fn set_record_link_groups(&mut self, record_link_groups: bool);
+    /// Header of the digest read by the last operation, empty if none was read
+    fn loaded_header(&self) -> &DigestHeader;
+    fn save_dd_file(
+        &self,
+        new_dd_file_dir: Option<PathBuf>,
+        format: DigestFormat,
+        compression: Compression,
+    ) -> Result<(), String>;
     fn start_generate(&mut self) -> Result<(), String>;
     fn start_validate(&mut self) -> Result<(), String>;
     fn start_fast_refresh(&mut self) -> Result<(), String>;
     fn start_full_refresh(&mut self) -> Result<(), String>;
     fn start_find_duplicates(&mut self) -> Result<(), String>;
+    fn start_migrate(&mut self, target: HashAlgorithm) -> Result<(), String>;
+    fn start_export(&mut self) -> Result<(), String>;
+
+    //used by a migrating engine to hash files on behalf of this engine
+    fn new_migration_state(&self) -> Box<dyn AnyHashState>;
+    fn parallel_large_file_hashing(&self) -> bool;
+    fn push_migrated_file(
+        &mut self,
+        path: PathBuf,
+        metadata: Option<FileMetadata>,
+        state: Box<dyn AnyHashState>,
+    );
+    fn push_migrated_symlink(&mut self, symlink: SymlinkSt);
+    fn push_migrated_dir(&mut self, dir: PathBuf);
 
     fn print_log_generate(&self);
     fn print_log_validate(&self);
     fn print_log_refresh(&self);
     fn print_log_find_duplicates(&self);
+    fn print_log_migrate(&self);
     fn print_log(&self);
 
     fn event_count_generate(&self) -> usize;
@@ -34,6 +88,7 @@ pub trait EngineAny {
     fn event_count_fast_refresh(&self) -> usize;
     fn event_count_full_refresh(&self) -> usize;
     fn event_count_find_duplicates(&self) -> usize;
+    fn event_count_migrate(&self) -> usize;
 }
 
 #[derive(PartialEq)]
@@ -43,13 +98,14 @@ enum Mode {
     FastRefresh,
     FullRefresh,
     FindDuplicates,
+    Migrate,
+    Export,
 }
 
 pub struct Engine<H>
 where
     H: HashValue,
 {
-    hash_type: HashType,
     dd_file_path: PathBuf,
     base_path: PathBuf,
     mode: Option<Mode>,
@@ -57,6 +113,21 @@ where
     primary_ds: Vec<FileSt<H>>,
     secondary_ds: Vec<FileSt<H>>,
 
+    //links recorded with SymlinkPolicy::Record, from disk (primary) and the digest (secondary)
+    primary_symlinks: Vec<SymlinkSt>,
+    secondary_symlinks: Vec<SymlinkSt>,
+
+    //directories, only recorded on request, from disk (primary) and the digest (secondary)
+    primary_dirs: Vec<PathBuf>,
+    secondary_dirs: Vec<PathBuf>,
+
+    //paths that couldn't be read while walking, and the digest entries under them, which a
+    //refresh keeps as they were
+    walk_errors: Vec<WalkError>,
+    unwalked_ds: Vec<FileSt<H>>,
+    //special files and other filesystems the walk left out
+    skipped_entries: Vec<SkippedEntry>,
+
     hashing_error_index: Vec<usize>,
     invalid_hash_index: Vec<usize>,
 
@@ -70,23 +141,64 @@ where
     crosscheck_primary_orphans_index: Vec<usize>,
     crosscheck_secondary_orphan_but_duplicate_index: Vec<usize>,
 
+    //files whose hash changed, but whose digest entry has no size and date to tell why
+    metadata_unavailable_index: Vec<usize>,
+
+    //files with intact content, but a different mode or owner than in the digest
+    permissions_changed_index: Vec<usize>,
+    ownership_changed_index: Vec<usize>,
+
+    //links on disk that point somewhere else than in the digest, or nowhere
+    retargeted_symlinks_index: Vec<usize>,
+    dangling_symlinks_index: Vec<usize>,
+
+    //files that were hardlinks of others in the digest, but are separate copies by now
+    broken_hardlinks_index: Vec<usize>,
+
+    //directories only on disk (primary), and only in the digest (secondary)
+    added_dirs_index: Vec<usize>,
+    removed_dirs_index: Vec<usize>,
+
+    //digest entries left out of a refresh, as they are excluded by now
+    excluded_entries_count: usize,
+
     duplicate_files_index: Vec<Vec<usize>>,
+
+    migration_target: Option<Box<dyn EngineAny>>,
+
+    //header of the digest that was read, empty if none was read or it records none
+    loaded_header: DigestHeader,
+
+    signing_key: Option<SigningKey>,
+    signature_policy: SignaturePolicy,
+    record_unix_metadata: bool,
+    symlink_policy: SymlinkPolicy,
+    ignore_patterns: Vec<String>,
+    one_file_system: bool,
+    record_dirs: bool,
+    record_link_groups: bool,
 }
 
 impl<H: HashValue + Sync + Send + std::fmt::Debug> EngineAny for Engine<H>
 where
     H: HashValue,
 {
-    fn new(hash_type: HashType) -> Self
+    fn new() -> Self
     where
         Self: Sized,
     {
         Engine {
-            hash_type,
             dd_file_path: PathBuf::new(),
             base_path: PathBuf::new(),
             primary_ds: Vec::new(),
             secondary_ds: Vec::new(),
+            primary_symlinks: Vec::new(),
+            secondary_symlinks: Vec::new(),
+            primary_dirs: Vec::new(),
+            secondary_dirs: Vec::new(),
+            walk_errors: Vec::new(),
+            skipped_entries: Vec::new(),
+            unwalked_ds: Vec::new(),
             mode: None,
             hashing_error_index: Vec::new(),
             invalid_hash_index: Vec::new(),
@@ -98,7 +210,26 @@ where
             crosscheck_secondary_orphans_index: Vec::new(),
             crosscheck_primary_orphans_index: Vec::new(),
             crosscheck_secondary_orphan_but_duplicate_index: Vec::new(),
+            metadata_unavailable_index: Vec::new(),
+            permissions_changed_index: Vec::new(),
+            ownership_changed_index: Vec::new(),
+            retargeted_symlinks_index: Vec::new(),
+            dangling_symlinks_index: Vec::new(),
+            broken_hardlinks_index: Vec::new(),
+            added_dirs_index: Vec::new(),
+            removed_dirs_index: Vec::new(),
+            excluded_entries_count: 0,
             duplicate_files_index: Vec::new(),
+            migration_target: None,
+            loaded_header: DigestHeader::default(),
+            signing_key: None,
+            signature_policy: SignaturePolicy::Ignore,
+            record_unix_metadata: false,
+            symlink_policy: SymlinkPolicy::default(),
+            ignore_patterns: Vec::new(),
+            one_file_system: false,
+            record_dirs: false,
+            record_link_groups: false,
         }
     }
 
@@ -107,58 +238,140 @@ where
         self.base_path = dir_path;
     }
 
-    fn save_dd_file(&self, new_dd_file_dir: Option<PathBuf>) -> Result<(), String> {
-        //Create the dd file path
-        let dd_file_path = match new_dd_file_dir {
-            //Save in the specified directory
-            Some(mut dir) => {
-                let now = chrono::Local::now();
-                let date_time = now.format("%Y-%m-%d_%H-%M");
-                dir.push(format!(
-                    "{}.{}",
-                    date_time,
-                    hash_type_to_suffix(&self.hash_type)
-                ));
-                dir
-            }
-            None => {
-                //Fallback to the executable directory
-                let mut path = std::env::current_exe().unwrap();
-                path.pop();
-                path.push(format!("digest.{}", hash_type_to_suffix(&self.hash_type)));
+    fn set_signing_key(&mut self, signing_key: Option<SigningKey>) {
+        //A migration saves the digest of the new hash type
+        if let Some(target) = &mut self.migration_target {
+            target.set_signing_key(signing_key.clone());
+        }
 
-                path
-            }
+        self.signing_key = signing_key;
+    }
+
+    fn set_signature_policy(&mut self, policy: SignaturePolicy) {
+        self.signature_policy = policy;
+    }
+
+    fn set_record_unix_metadata(&mut self, record: bool) {
+        self.record_unix_metadata = record;
+    }
+
+    fn set_symlink_policy(&mut self, policy: SymlinkPolicy) {
+        self.symlink_policy = policy;
+    }
+
+    fn set_ignore_patterns(&mut self, patterns: Vec<String>) {
+        self.ignore_patterns = patterns;
+    }
+
+    fn set_one_file_system(&mut self, one_file_system: bool) {
+        self.one_file_system = one_file_system;
+    }
+
+    fn set_record_dirs(&mut self, record_dirs: bool) {
+        self.record_dirs = record_dirs;
+    }
+
+    fn set_record_link_groups(&mut self, record_link_groups: bool) {
+        self.record_link_groups = record_link_groups;
+    }
+
+    fn loaded_header(&self) -> &DigestHeader {
+        &self.loaded_header
+    }
+
+    fn save_dd_file(
+        &self,
+        new_dd_file_dir: Option<PathBuf>,
+        format: DigestFormat,
+        compression: Compression,
+    ) -> Result<(), String> {
+        //A migration saves the digest of the new hash type
+        if let Some(target) = &self.migration_target {
+            return target.save_dd_file(new_dd_file_dir, format, compression);
+        }
+
+        //Checksum files hold a single hash, so multi-hash digests are split into one file per hash
+        let suffixes: Vec<String> = match format {
+            DigestFormat::Native => vec![H::dd_suffix().to_string()],
+            DigestFormat::Coreutils => H::sum_suffixes().iter().map(|s| s.to_string()).collect(),
+            DigestFormat::Sfv if H::sfv_compatible() => vec![SFV_SUFFIX.to_string()],
+            DigestFormat::Sfv => Vec::new(),
+            DigestFormat::Json => vec![format!("{}.{}", H::dd_suffix(), JSON_SUFFIX)],
+            DigestFormat::Binary => vec![format!("{}.{}", H::dd_suffix(), BINARY_SUFFIX)],
         };
 
+        if suffixes.is_empty() {
+            return Err(format!(
+                "{} digests cannot be saved in the {:?} format",
+                H::signature_to_string(),
+                format
+            ));
+        }
+
         let valid_files: Vec<&FileSt<H>> = self
             .primary_ds
             .iter()
             .enumerate()
             .filter(|(index, _)| !self.hashing_error_index.contains(index))
             .map(|(_, file)| file)
+            .chain(self.unwalked_ds.iter())
             .collect();
 
-        write_dd(&valid_files, &dd_file_path, &self.base_path)
-            .map_err(|e| format!("Failed to write dd file: {}", e))
+        let header = self.new_digest_header();
+
+        for mut suffix in suffixes {
+            if let Some(compression_suffix) = compression.suffix() {
+                suffix = format!("{}.{}", suffix, compression_suffix);
+            }
+
+            //Create the dd file path
+            let dd_file_path = match &new_dd_file_dir {
+                //Save in the specified directory
+                Some(dir) => {
+                    let now = chrono::Local::now();
+                    let date_time = now.format("%Y-%m-%d_%H-%M");
+                    dir.join(format!("{}.{}", date_time, suffix))
+                }
+                None => {
+                    //Fallback to the executable directory
+                    let mut path = std::env::current_exe().unwrap();
+                    path.pop();
+                    path.push(format!("digest.{}", suffix));
+
+                    path
+                }
+            };
+
+            write_dd(
+                &valid_files,
+                &self.primary_symlinks,
+                &self.primary_dirs,
+                &dd_file_path,
+                &self.base_path,
+                &header,
+                self.signing_key.as_ref(),
+            )
+            .map_err(|e| format!("Failed to write dd file: {}", e))?;
+        }
+
+        Ok(())
     }
 
     // ############################################################################################
 
     fn start_generate(&mut self) -> Result<(), String> {
-        if let None = self.mode {
+        if self.mode.is_none() {
             self.mode = Some(Mode::Generate);
         } else {
             return Err("BUG: Engine is already in a mode".to_string());
         }
 
-        let mut dir_walker: DirectoryWalker<H> = DirectoryWalker::new(self.base_path.clone());
-        match dir_walker.walk() {
-            Err(e) => return Err(format!("Error when walking the directory: {}", e)),
-            _ => {}
-        }
+        let filter = PathFilter::new(self.base_path.clone(), self.ignore_patterns.clone())?;
+        self.walk_base_path(filter)?;
 
-        self.primary_ds = dir_walker.into_files();
+        if !self.record_unix_metadata {
+            strip_unix_metadata(&mut self.primary_ds);
+        }
 
         match calculate_hashes(
             &mut self.primary_ds,
@@ -179,14 +392,18 @@ where
     }
 
     fn start_validate(&mut self) -> Result<(), String> {
-        if let None = self.mode {
+        if self.mode.is_none() {
             self.mode = Some(Mode::Validate);
         } else {
             return Err("BUG: Engine is already in a mode".to_string());
         }
 
-        self.primary_ds = read_dd(&self.dd_file_path, &self.base_path)
-            .map_err(|e| format!("Failed to read dd file: {}", e))?;
+        //Read once, so the digest parsed is the one whose signature was checked
+        let data =
+            fs::read(&self.dd_file_path).map_err(|e| format!("Failed to read dd file: {}", e))?;
+        verify_dd_file(&self.dd_file_path, &data, &self.signature_policy)?;
+
+        self.load_primary_dd_data(&data)?;
 
         match calculate_hashes(
             &mut self.primary_ds,
@@ -225,28 +442,33 @@ where
             }
         }
 
+        self.find_unix_metadata_changes_on_disk();
+
         Ok(())
     }
 
     fn start_fast_refresh(&mut self) -> Result<(), String> {
-        if let None = self.mode {
+        if self.mode.is_none() {
             self.mode = Some(Mode::FastRefresh);
         } else {
             return Err("BUG: Engine is already in a mode".to_string());
         }
 
-        let mut dir_walker: DirectoryWalker<H> = DirectoryWalker::new(self.base_path.clone());
-        match dir_walker.walk() {
-            Err(e) => return Err(format!("Error when walking the directory: {}", e)),
-            _ => {}
-        }
+        //Secondary snapshot is from the file
+        self.load_secondary_dd()?;
+
+        //Entries that are excluded by now are dropped, instead of reported as missing on disk
+        let filter = PathFilter::new(self.base_path.clone(), self.ignore_patterns.clone())?;
+        self.exclude_secondary_entries(&filter);
 
         //Primary snapshot is from the directory
-        self.primary_ds = dir_walker.into_files();
+        self.walk_base_path(filter)?;
+        self.keep_unwalked_entries();
 
-        //Secondary snapshot is from the file
-        self.secondary_ds = read_dd(&self.dd_file_path, &self.base_path)
-            .map_err(|e| format!("Failed to read dd file: {}", e))?;
+        //The refreshed digest records the mode and ownership only if the digest did
+        if !has_unix_metadata(&self.secondary_ds) {
+            strip_unix_metadata(&mut self.primary_ds);
+        }
 
         //A hashmap of Path->Index for Primary snap
         let mut primary_paths_index: HashMap<&Path, usize> = HashMap::new();
@@ -263,7 +485,6 @@ where
         }
 
         let mut only_in_primary_index: Vec<usize> = Vec::new();
-        let only_in_secondary_index: Vec<usize>;
         let mut in_both_index: Vec<(usize, usize)> = Vec::new(); // (primary_id, secondary_id)
 
         //Get the indexes for the files
@@ -274,7 +495,7 @@ where
             }
         }
 
-        only_in_secondary_index = secondary_paths_index
+        let only_in_secondary_index: Vec<usize> = secondary_paths_index
             .iter()
             .filter(|(&path, _)| !primary_paths_index.contains_key(path))
             .map(|(_, &idx)| idx)
@@ -287,22 +508,22 @@ where
             let primary_file = &self.primary_ds[*primary_index];
             let secondary_file = &self.secondary_ds[*secondary_index];
 
-            let primary_secs = primary_file
+            let primary_metadata = primary_file
                 .metadata
-                .last_modified
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
+                .as_ref()
+                .expect("BUG: In fast refresh, file on disk has no metadata");
 
-            let secondary_secs = secondary_file
-                .metadata
-                .last_modified
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
+            //Without metadata (eg. SFV files) a change cannot be detected, so the file is hashed
+            let secondary_metadata = match secondary_file.metadata.as_ref() {
+                Some(metadata) => metadata,
+                None => {
+                    dirty_files_index.push((*primary_index, *secondary_index));
+                    continue;
+                }
+            };
 
-            if primary_file.metadata.size != secondary_file.metadata.size
-                || primary_secs != secondary_secs
+            if primary_metadata.size != secondary_metadata.size
+                || !primary_metadata.same_last_modified(secondary_metadata)
             {
                 dirty_files_index.push((*primary_index, *secondary_index));
                 continue;
@@ -333,6 +554,12 @@ where
             }
         }
 
+        //Files that failed to hash are only reported as such
+        let hashing_errors: HashSet<usize> = self.hashing_error_index.iter().copied().collect();
+        in_both_index.retain(|(primary_index, _)| !hashing_errors.contains(primary_index));
+        dirty_files_index.retain(|(primary_index, _)| !hashing_errors.contains(primary_index));
+        only_in_primary_index.retain(|index| !hashing_errors.contains(index));
+
         //Mark files that have invalid hashes to generate a report later
 
         for (primary_file_index, secondary) in &dirty_files_index {
@@ -350,23 +577,37 @@ where
                 .as_ref()
                 .expect("BUG: In fast refresh, 'loaded hash' has no hash");
 
+            let primary_metadata = primary_file
+                .metadata
+                .as_ref()
+                .expect("BUG: In fast refresh, file on disk has no metadata");
+            let secondary_metadata = match secondary_file.metadata.as_ref() {
+                Some(metadata) => metadata,
+                None => {
+                    if !primary_hash.equals(secondary_hash) {
+                        self.metadata_unavailable_index.push(*primary_file_index);
+                    }
+                    continue;
+                }
+            };
+
             if !primary_hash.equals(secondary_hash) {
                 //both date and size are same
-                if primary_file.metadata.size == secondary_file.metadata.size
-                    && primary_file.metadata.last_modified == secondary_file.metadata.last_modified
+                if primary_metadata.size == secondary_metadata.size
+                    && primary_metadata.same_last_modified(secondary_metadata)
                 {
                     self.invalid_hash_index.push(*primary_file_index);
                 }
                 //only the size is different
-                else if primary_file.metadata.size != secondary_file.metadata.size
-                    && primary_file.metadata.last_modified == secondary_file.metadata.last_modified
+                else if primary_metadata.size != secondary_metadata.size
+                    && primary_metadata.same_last_modified(secondary_metadata)
                 {
                     self.dirty_potentially_invalid_s_files_index
                         .push(*primary_file_index);
                 }
                 //only the date is different
-                else if primary_file.metadata.size == secondary_file.metadata.size
-                    && primary_file.metadata.last_modified != secondary_file.metadata.last_modified
+                else if primary_metadata.size == secondary_metadata.size
+                    && !primary_metadata.same_last_modified(secondary_metadata)
                 {
                     self.dirty_potentially_invalid_d_files_index
                         .push(*primary_file_index);
@@ -390,7 +631,7 @@ where
             if let Some(hash) = &file.calculated_hash {
                 primary_new_files_hash_index
                     .entry(hash.clone())
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .push(*index);
             } else {
                 panic!("File at index {} has no calculated hash", index);
@@ -404,7 +645,7 @@ where
             if let Some(hash) = &file.loaded_hash {
                 secondary_files_hash_index
                     .entry(hash.clone())
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .push(index);
             } else {
                 panic!("File at index {} has no loaded hash", index);
@@ -486,6 +727,11 @@ where
             primary_file.calculated_hash = Some(secondary_hash.clone());
         }
 
+        self.find_unix_metadata_changes(&in_both_index);
+        self.find_symlink_changes();
+        self.find_broken_hardlinks(&in_both_index);
+        self.find_dir_changes();
+
         //DEBUG: print all primary files that don't have a hash
         #[cfg(debug_assertions)]
         {
@@ -501,20 +747,22 @@ where
     }
 
     fn start_full_refresh(&mut self) -> Result<(), String> {
-        if let None = self.mode {
+        if self.mode.is_none() {
             self.mode = Some(Mode::FullRefresh);
         } else {
             return Err("BUG: Engine is already in a mode".to_string());
         }
 
-        let mut dir_walker: DirectoryWalker<H> = DirectoryWalker::new(self.base_path.clone());
-        match dir_walker.walk() {
-            Err(e) => return Err(format!("Error when walking the directory: {}", e)),
-            _ => {}
-        }
+        //Secondary snapshot is from the file
+        self.load_secondary_dd()?;
+
+        //Entries that are excluded by now are dropped, instead of reported as missing on disk
+        let filter = PathFilter::new(self.base_path.clone(), self.ignore_patterns.clone())?;
+        self.exclude_secondary_entries(&filter);
 
         //Primary snapshot is from disk
-        self.primary_ds = dir_walker.into_files();
+        self.walk_base_path(filter)?;
+        self.keep_unwalked_entries();
 
         match calculate_hashes(
             &mut self.primary_ds,
@@ -531,9 +779,10 @@ where
             Err(e) => return Err(format!("Failed to calculate hashes: {}", e)),
         }
 
-        //Secondary snapshot is from the file
-        self.secondary_ds = read_dd(&self.dd_file_path, &self.base_path)
-            .map_err(|e| format!("Failed to read dd file: {}", e))?;
+        //The refreshed digest records the mode and ownership only if the digest did
+        if !has_unix_metadata(&self.secondary_ds) {
+            strip_unix_metadata(&mut self.primary_ds);
+        }
 
         //A hashmap of Path->Index for Primary snap
         let mut primary_paths_index: HashMap<&Path, usize> = HashMap::new();
@@ -550,7 +799,6 @@ where
         }
 
         let mut only_in_primary_index: Vec<usize> = Vec::new();
-        let only_in_secondary_index: Vec<usize>;
         let mut in_both_index: Vec<(usize, usize)> = Vec::new(); // (primary_id, secondary_id)
 
         //Get the indexes for the files
@@ -561,12 +809,17 @@ where
             }
         }
 
-        only_in_secondary_index = secondary_paths_index
+        let only_in_secondary_index: Vec<usize> = secondary_paths_index
             .iter()
             .filter(|(&path, _)| !primary_paths_index.contains_key(path))
             .map(|(_, &idx)| idx)
             .collect();
 
+        //Files that failed to hash are only reported as such
+        let hashing_errors: HashSet<usize> = self.hashing_error_index.iter().copied().collect();
+        in_both_index.retain(|(primary_index, _)| !hashing_errors.contains(primary_index));
+        only_in_primary_index.retain(|index| !hashing_errors.contains(index));
+
         let mut dirty_files_index: Vec<(usize, usize)> = Vec::new();
 
         //Get dirty files (files that have different hash)
@@ -597,22 +850,34 @@ where
             let primary_file = &self.primary_ds[*primary_file_index];
             let secondary_file = &self.secondary_ds[*secondary];
 
+            let primary_metadata = primary_file
+                .metadata
+                .as_ref()
+                .expect("BUG: In full refresh, file on disk has no metadata");
+            let secondary_metadata = match secondary_file.metadata.as_ref() {
+                Some(metadata) => metadata,
+                None => {
+                    self.metadata_unavailable_index.push(*primary_file_index);
+                    continue;
+                }
+            };
+
             //both date and size are same
-            if primary_file.metadata.size == secondary_file.metadata.size
-                && primary_file.metadata.last_modified == secondary_file.metadata.last_modified
+            if primary_metadata.size == secondary_metadata.size
+                && primary_metadata.same_last_modified(secondary_metadata)
             {
                 self.invalid_hash_index.push(*primary_file_index);
             }
             //only the size is different
-            else if primary_file.metadata.size != secondary_file.metadata.size
-                && primary_file.metadata.last_modified == secondary_file.metadata.last_modified
+            else if primary_metadata.size != secondary_metadata.size
+                && primary_metadata.same_last_modified(secondary_metadata)
             {
                 self.dirty_potentially_invalid_s_files_index
                     .push(*primary_file_index);
             }
             //only the date is different
-            else if primary_file.metadata.size == secondary_file.metadata.size
-                && primary_file.metadata.last_modified != secondary_file.metadata.last_modified
+            else if primary_metadata.size == secondary_metadata.size
+                && !primary_metadata.same_last_modified(secondary_metadata)
             {
                 self.dirty_potentially_invalid_d_files_index
                     .push(*primary_file_index);
@@ -629,11 +894,15 @@ where
         let mut primary_files_hash_index: HashMap<H, Vec<usize>> = HashMap::new();
 
         for primary_index in 0..self.primary_ds.len() {
+            if hashing_errors.contains(&primary_index) {
+                continue;
+            }
+
             let file = &self.primary_ds[primary_index];
             if let Some(hash) = &file.calculated_hash {
                 primary_files_hash_index
                     .entry(hash.clone())
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .push(primary_index);
             } else {
                 panic!("File at index {} has no calculated hash", primary_index);
@@ -672,18 +941,22 @@ where
             }
         }
 
+        self.find_unix_metadata_changes(&in_both_index);
+        self.find_symlink_changes();
+        self.find_broken_hardlinks(&in_both_index);
+        self.find_dir_changes();
+
         Ok(())
     }
 
     fn start_find_duplicates(&mut self) -> Result<(), String> {
-        if let None = self.mode {
+        if self.mode.is_none() {
             self.mode = Some(Mode::FindDuplicates);
         } else {
             return Err("BUG: Engine is already in a mode".to_string());
         }
 
-        self.primary_ds = read_dd(&self.dd_file_path, &self.base_path)
-            .map_err(|e| format!("Failed to read dd file: {}", e))?;
+        self.load_primary_dd()?;
 
         let mut primary_files_hash_index: HashMap<H, Vec<usize>> = HashMap::new();
 
@@ -692,7 +965,7 @@ where
             if let Some(hash) = &file.loaded_hash {
                 primary_files_hash_index
                     .entry(hash.clone())
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .push(primary_index);
             } else {
                 panic!("File at index {} has no loaded hash", primary_index);
@@ -708,6 +981,150 @@ where
         Ok(())
     }
 
+    fn start_migrate(&mut self, target: HashAlgorithm) -> Result<(), String> {
+        if self.mode.is_none() {
+            self.mode = Some(Mode::Migrate);
+        } else {
+            return Err("BUG: Engine is already in a mode".to_string());
+        }
+
+        self.load_primary_dd()?;
+
+        let mut target = target.create_engine();
+        target.set_paths(PathBuf::new(), self.base_path.clone());
+
+        //Every file is read once, feeding both the old and the new hash
+        let target_parallel = target.parallel_large_file_hashing();
+        let mut jobs: Vec<MigrationJob<H>> = self
+            .primary_ds
+            .iter_mut()
+            .map(|file| MigrationJob {
+                file,
+                target_state: Some(target.new_migration_state()),
+                target_parallel,
+            })
+            .collect();
+
+        match calculate_hashes(
+            &mut jobs,
+            SMALL_FILE_THREADS,
+            LARGE_FILE_THREADS,
+            SMALL_FILE_SIZE_THRESHOLD,
+            None,
+        ) {
+            Ok(error_indexes) => {
+                if let Some(indexes) = error_indexes {
+                    self.hashing_error_index = indexes;
+                }
+            }
+            Err(e) => return Err(format!("Failed to calculate hashes: {}", e)),
+        }
+
+        let hashing_errors: HashSet<usize> = self.hashing_error_index.iter().cloned().collect();
+
+        //Only files that still match the old hash are carried over into the new digest
+        for (index, job) in jobs.iter_mut().enumerate() {
+            if hashing_errors.contains(&index) {
+                continue;
+            }
+
+            let calculated_hash = job
+                .file
+                .calculated_hash
+                .as_ref()
+                .expect("BUG: In migration, 'calculated hash' has no hash");
+            let loaded_hash = job
+                .file
+                .loaded_hash
+                .as_ref()
+                .expect("BUG: In migration, 'loaded hash' has no hash");
+
+            if !loaded_hash.equals(calculated_hash) {
+                self.invalid_hash_index.push(index);
+                continue;
+            }
+
+            let target_state = job
+                .target_state
+                .take()
+                .expect("BUG: In migration, file has no target hash state");
+
+            target.push_migrated_file(
+                job.file.path.clone(),
+                job.file.metadata.clone(),
+                target_state,
+            );
+        }
+
+        //Links have no content to rehash
+        target.set_symlink_policy(self.loaded_header.symlink_policy.unwrap_or_default());
+        target.set_ignore_patterns(self.loaded_header.ignore_patterns.clone());
+        target.set_one_file_system(self.loaded_header.one_file_system.unwrap_or(false));
+        for symlink in self.primary_symlinks.iter() {
+            target.push_migrated_symlink(symlink.clone());
+        }
+        target.set_record_dirs(self.loaded_header.record_dirs.unwrap_or(false));
+        target.set_record_link_groups(self.loaded_header.record_link_groups.unwrap_or(false));
+        for dir in self.primary_dirs.iter() {
+            target.push_migrated_dir(dir.clone());
+        }
+
+        self.migration_target = Some(target);
+
+        Ok(())
+    }
+
+    fn start_export(&mut self) -> Result<(), String> {
+        if self.mode.is_none() {
+            self.mode = Some(Mode::Export);
+        } else {
+            return Err("BUG: Engine is already in a mode".to_string());
+        }
+
+        //Paths are kept relative to the digest, so the export stays relative as well
+        self.base_path = PathBuf::new();
+        self.load_primary_dd()?;
+
+        //Nothing is hashed, the loaded hashes are written as they are
+        for file in self.primary_ds.iter_mut() {
+            file.calculated_hash = file.loaded_hash.clone();
+        }
+
+        Ok(())
+    }
+
+    fn new_migration_state(&self) -> Box<dyn AnyHashState> {
+        Box::new(H::new_state())
+    }
+
+    fn parallel_large_file_hashing(&self) -> bool {
+        H::parallel_large_file_hashing()
+    }
+
+    fn push_migrated_file(
+        &mut self,
+        path: PathBuf,
+        metadata: Option<FileMetadata>,
+        state: Box<dyn AnyHashState>,
+    ) {
+        let state = state
+            .into_any()
+            .downcast::<H::State>()
+            .expect("BUG: Migrated file was hashed with a different hash type");
+
+        let mut file = FileSt::new(path, None, metadata);
+        file.calculated_hash = Some(H::from_state(*state));
+        self.primary_ds.push(file);
+    }
+
+    fn push_migrated_symlink(&mut self, symlink: SymlinkSt) {
+        self.primary_symlinks.push(symlink);
+    }
+
+    fn push_migrated_dir(&mut self, dir: PathBuf) {
+        self.primary_dirs.push(dir);
+    }
+
     // ############################################################################################
 
     fn print_log_generate(&self) {
@@ -719,6 +1136,9 @@ where
             return;
         }
 
+        self.print_log_walk_errors();
+        self.print_log_skipped_entries();
+
         //print all the files that failed to hash
         println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
         println!(
@@ -768,6 +1188,8 @@ where
                 println!("{}", self.primary_ds[*index].path.display());
             }
         }
+
+        self.print_log_unix_metadata_changes();
     }
     fn print_log_refresh(&self) {
         if self.mode != Some(Mode::FastRefresh) && self.mode != Some(Mode::FullRefresh) {
@@ -784,6 +1206,15 @@ where
         let warning = self.dirty_potentially_invalid_d_files_index.len()
             + self.dirty_potentially_invalid_s_files_index.len()
             + self.dirty_potentially_invalid_sd_files_index.len()
+            + self.metadata_unavailable_index.len()
+            + self.permissions_changed_index.len()
+            + self.ownership_changed_index.len()
+            + self.retargeted_symlinks_index.len()
+            + self.dangling_symlinks_index.len()
+            + self.broken_hardlinks_index.len()
+            + self.added_dirs_index.len()
+            + self.removed_dirs_index.len()
+            + self.skipped_entries.len()
             + self.crosscheck_secondary_orphans_index.len()
             + self.crosscheck_primary_orphans_index.len();
 
@@ -791,7 +1222,8 @@ where
             self.crosscheck_secondary_orphan_but_duplicate_index.len();
         }
 
-        let error = self.invalid_hash_index.len() + self.hashing_error_index.len();
+        let error =
+            self.invalid_hash_index.len() + self.hashing_error_index.len() + self.walk_errors.len();
 
         // --------------
 
@@ -811,11 +1243,43 @@ where
 
         // --------------
 
+        let no_metadata = self
+            .secondary_ds
+            .iter()
+            .filter(|file| file.metadata.is_none())
+            .count();
+        if self.excluded_entries_count > 0 {
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::BrightYellow,
+                    &format!(
+                        "Excluded: {} entries in the digest file are excluded by the ignore patterns by now, and were left out.",
+                        self.excluded_entries_count
+                    )
+                )
+            );
+        }
+
+        if no_metadata > 0 {
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::BrightYellow,
+                    &format!(
+                        "Metadata unavailable: {} entries in the digest file have no size and last modified date, their files were hashed and compared by hash only.",
+                        no_metadata
+                    )
+                )
+            );
+        }
+
         if self.mode == Some(Mode::FullRefresh) {
             let invalid = self.invalid_hash_index.len()
                 + self.dirty_potentially_invalid_s_files_index.len()
                 + self.dirty_potentially_invalid_d_files_index.len()
-                + self.dirty_potentially_invalid_sd_files_index.len();
+                + self.dirty_potentially_invalid_sd_files_index.len()
+                + self.metadata_unavailable_index.len();
             println!(
                 "{}",
                 colorize_txt(
@@ -840,6 +1304,9 @@ where
             )
         );
 
+        self.print_log_walk_errors();
+        self.print_log_skipped_entries();
+
         //print all the files that failed to hash
         if !self.hashing_error_index.is_empty() {
             println!("{}", colorize_txt(TextColor::BrightRed, "######"));
@@ -931,6 +1398,31 @@ where
             }
         }
 
+        //print all the files that changed, but have no metadata in the digest file
+        if !self.metadata_unavailable_index.is_empty() {
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::BrightYellow,
+                    &format!(
+                        "({}) Files that have a different hash, but no size or last modified date in the digest file (modified or corrupted):",
+                        self.metadata_unavailable_index.len()
+                    )
+                )
+            );
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+
+            for index in self.metadata_unavailable_index.iter() {
+                println!("{}", self.primary_ds[*index].path.display());
+            }
+        }
+
+        self.print_log_unix_metadata_changes();
+        self.print_log_symlink_changes();
+        self.print_log_broken_hardlinks();
+        self.print_log_dir_changes();
+
         //print all the files that couldn't be crosschecked, only in secondary
         if !self.crosscheck_secondary_orphans_index.is_empty() {
             println!("{}", colorize_txt(TextColor::BrightMagenta, "######"));
@@ -1071,6 +1563,65 @@ where
         }
     }
 
+    fn print_log_migrate(&self) {
+        if self.mode != Some(Mode::Migrate) {
+            println!(
+                "{}",
+                colorize_txt(TextColor::Red, "BUG: Engine is not in migrate mode")
+            );
+            return;
+        }
+
+        let migrated =
+            self.primary_ds.len() - self.hashing_error_index.len() - self.invalid_hash_index.len();
+
+        println!(
+            "{}",
+            colorize_txt(
+                TextColor::BrightYellow,
+                &format!(
+                    "{} out of {} files were verified and migrated to the new hash type.",
+                    migrated,
+                    self.primary_ds.len()
+                )
+            )
+        );
+
+        //print all the files that failed to hash
+        if !self.hashing_error_index.is_empty() {
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+            println!(
+                "{}",
+                colorize_txt(TextColor::BrightYellow, "Files that failed to hash:")
+            );
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+
+            for index in self.hashing_error_index.iter() {
+                println!("{}", self.primary_ds[*index].path.display());
+            }
+        }
+
+        //print all the files that did not match the old hash, these are not in the new digest
+        if !self.invalid_hash_index.is_empty() {
+            println!("{}", colorize_txt(TextColor::BrightRed, "######"));
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::BrightRed,
+                    &format!(
+                        "({}) Files that do not match the old hash and were left out of the new digest:",
+                        self.invalid_hash_index.len()
+                    )
+                )
+            );
+            println!("{}", colorize_txt(TextColor::BrightRed, "######"));
+
+            for index in self.invalid_hash_index.iter() {
+                println!("{}", self.primary_ds[*index].path.display());
+            }
+        }
+    }
+
     fn print_log(&self) {
         match &self.mode {
             None => {
@@ -1092,25 +1643,43 @@ where
                 Mode::FindDuplicates => {
                     self.print_log_find_duplicates();
                 }
+                Mode::Migrate => {
+                    self.print_log_migrate();
+                }
+                Mode::Export => {}
             },
         }
     }
 
     fn event_count_generate(&self) -> usize {
-        self.hashing_error_index.len()
+        self.hashing_error_index.len() + self.walk_errors.len() + self.skipped_entries.len()
     }
 
     fn event_count_validate(&self) -> usize {
-        self.hashing_error_index.len() + self.invalid_hash_index.len()
+        self.hashing_error_index.len()
+            + self.invalid_hash_index.len()
+            + self.permissions_changed_index.len()
+            + self.ownership_changed_index.len()
     }
 
     fn event_count_fast_refresh(&self) -> usize {
         self.hashing_error_index.len()
+            + self.walk_errors.len()
+            + self.skipped_entries.len()
             + self.invalid_hash_index.len()
             + self.dirty_potentially_invalid_d_files_index.len()
             + self.dirty_potentially_invalid_s_files_index.len()
             + self.dirty_potentially_invalid_sd_files_index.len()
             + self.dirty_valid_files_index.len()
+            + self.metadata_unavailable_index.len()
+            + self.permissions_changed_index.len()
+            + self.ownership_changed_index.len()
+            + self.retargeted_symlinks_index.len()
+            + self.dangling_symlinks_index.len()
+            + self.broken_hardlinks_index.len()
+            + self.added_dirs_index.len()
+            + self.removed_dirs_index.len()
+            + self.excluded_entries_count
             + self.crosscheck_secondary_orphans_index.len()
             + self.crosscheck_primary_orphans_index.len()
             + self.crosscheck_secondary_orphan_but_duplicate_index.len()
@@ -1119,10 +1688,21 @@ where
 
     fn event_count_full_refresh(&self) -> usize {
         self.hashing_error_index.len()
+            + self.walk_errors.len()
+            + self.skipped_entries.len()
             + self.invalid_hash_index.len()
             + self.dirty_potentially_invalid_d_files_index.len()
             + self.dirty_potentially_invalid_s_files_index.len()
             + self.dirty_potentially_invalid_sd_files_index.len()
+            + self.metadata_unavailable_index.len()
+            + self.permissions_changed_index.len()
+            + self.ownership_changed_index.len()
+            + self.retargeted_symlinks_index.len()
+            + self.dangling_symlinks_index.len()
+            + self.broken_hardlinks_index.len()
+            + self.added_dirs_index.len()
+            + self.removed_dirs_index.len()
+            + self.excluded_entries_count
             + self.crosscheck_secondary_orphans_index.len()
             + self.crosscheck_primary_orphans_index.len()
             + self.crosscheck_primary_to_secondary_found_index.len()
@@ -1131,4 +1711,870 @@ where
     fn event_count_find_duplicates(&self) -> usize {
         self.duplicate_files_index.len()
     }
-}
\ No newline at end of file
+
+    fn event_count_migrate(&self) -> usize {
+        self.hashing_error_index.len() + self.invalid_hash_index.len()
+    }
+}
+
+impl<H: HashValue> Engine<H> {
+    fn load_primary_dd(&mut self) -> Result<(), String> {
+        let contents = read_dd(&self.dd_file_path, &self.base_path);
+        self.set_primary_dd(contents)
+    }
+
+    /// Same as load_primary_dd, from the digest file already read into data
+    fn load_primary_dd_data(&mut self, data: &[u8]) -> Result<(), String> {
+        let contents = read_dd_data(&self.dd_file_path, data, &self.base_path);
+        self.set_primary_dd(contents)
+    }
+
+    fn set_primary_dd(&mut self, contents: io::Result<DigestContents<H>>) -> Result<(), String> {
+        let contents = contents.map_err(|e| format!("Failed to read dd file: {}", e))?;
+
+        self.loaded_header = contents.header;
+        self.primary_ds = contents.files;
+        self.primary_symlinks = contents.symlinks;
+        self.primary_dirs = contents.dirs;
+        Ok(())
+    }
+
+    /// Reads the digest to refresh, the directory is then walked the way the digest was made
+    fn load_secondary_dd(&mut self) -> Result<(), String> {
+        let DigestContents {
+            header,
+            files,
+            symlinks,
+            dirs,
+        } = read_dd(&self.dd_file_path, &self.base_path)
+            .map_err(|e| format!("Failed to read dd file: {}", e))?;
+
+        //Digests without a policy were made before links were handled, and followed them
+        self.symlink_policy = header.symlink_policy.unwrap_or_default();
+        self.one_file_system = header.one_file_system.unwrap_or(false);
+        self.record_dirs = header.record_dirs.unwrap_or(false);
+        self.record_link_groups = header.record_link_groups.unwrap_or(false);
+
+        //The patterns of this run come last, so they win over the ones of the digest
+        let mut ignore_patterns = header.ignore_patterns.clone();
+        for pattern in self.ignore_patterns.drain(..) {
+            if !ignore_patterns.contains(&pattern) {
+                ignore_patterns.push(pattern);
+            }
+        }
+        self.ignore_patterns = ignore_patterns;
+        self.loaded_header = header;
+        self.secondary_ds = files;
+        self.secondary_symlinks = symlinks;
+        self.secondary_dirs = dirs;
+        Ok(())
+    }
+
+    /// Walks the base directory into the primary snapshot. Paths that can't be read are collected
+    /// in walk_errors, instead of failing the whole run.
+    fn walk_base_path(&mut self, filter: PathFilter) -> Result<(), String> {
+        let mut dir_walker: DirectoryWalker<H> =
+            DirectoryWalker::new(self.base_path.clone(), self.symlink_policy, filter);
+        dir_walker.tolerant = true;
+        dir_walker.one_file_system = self.one_file_system;
+        dir_walker.record_dirs = self.record_dirs;
+        if let Err(e) = dir_walker.walk() {
+            return Err(format!("Error when walking the directory: {}", e));
+        }
+
+        self.walk_errors = std::mem::take(&mut dir_walker.walk_errors);
+        self.skipped_entries = std::mem::take(&mut dir_walker.skipped);
+        self.primary_dirs = std::mem::take(&mut dir_walker.dirs);
+        (self.primary_ds, self.primary_symlinks) = dir_walker.into_files_and_symlinks();
+        if self.record_link_groups {
+            assign_link_groups(&mut self.primary_ds);
+        }
+        Ok(())
+    }
+
+    /// Moves the digest entries under paths that couldn't be walked out of the comparison, so
+    /// they are kept in the refreshed digest instead of being reported and dropped as missing
+    fn keep_unwalked_entries(&mut self) {
+        if self.walk_errors.is_empty() {
+            return;
+        }
+
+        //A directory whose listing failed part way is walked still, so only the entries the walk
+        //didn't find are kept
+        let walked: HashSet<PathBuf> = self
+            .primary_ds
+            .iter()
+            .map(|file| file.path.clone())
+            .chain(
+                self.primary_symlinks
+                    .iter()
+                    .map(|symlink| symlink.path.clone()),
+            )
+            .chain(self.primary_dirs.iter().cloned())
+            .collect();
+        let walk_errors = &self.walk_errors;
+        let is_unwalked = |path: &Path| {
+            !walked.contains(path) && walk_errors.iter().any(|e| path.starts_with(&e.path))
+        };
+
+        let (unwalked, walked_ds): (Vec<FileSt<H>>, Vec<FileSt<H>>) =
+            std::mem::take(&mut self.secondary_ds)
+                .into_iter()
+                .partition(|file| is_unwalked(&file.path));
+        self.secondary_ds = walked_ds;
+
+        //Nothing is hashed, the loaded hashes are written as they are
+        self.unwalked_ds = unwalked;
+        for file in self.unwalked_ds.iter_mut() {
+            file.calculated_hash = file.loaded_hash.clone();
+            //the groups are numbered anew from the walked files
+            if let Some(metadata) = &mut file.metadata {
+                metadata.link_group = None;
+            }
+        }
+
+        for symlink in self.secondary_symlinks.iter() {
+            if is_unwalked(&symlink.path) {
+                self.primary_symlinks.push(symlink.clone());
+            }
+        }
+
+        for dir in self.secondary_dirs.iter() {
+            if is_unwalked(dir) {
+                self.primary_dirs.push(dir.clone());
+            }
+        }
+        self.primary_dirs.sort();
+    }
+
+    fn print_log_walk_errors(&self) {
+        if self.walk_errors.is_empty() {
+            return;
+        }
+
+        println!("{}", colorize_txt(TextColor::BrightRed, "######"));
+        println!(
+            "{}",
+            colorize_txt(
+                TextColor::BrightRed,
+                &format!(
+                    "({}) Paths that couldn't be read while walking the directory:",
+                    self.walk_errors.len()
+                )
+            )
+        );
+        println!("{}", colorize_txt(TextColor::BrightRed, "######"));
+
+        for walk_error in self.walk_errors.iter() {
+            println!("{}: {}", walk_error.path.display(), walk_error.error);
+        }
+
+        if !self.unwalked_ds.is_empty() {
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::BrightYellow,
+                    &format!(
+                        "{} entries in the digest file are under these paths, and were kept as they were.",
+                        self.unwalked_ds.len()
+                    )
+                )
+            );
+        }
+    }
+
+    fn print_log_skipped_entries(&self) {
+        if self.skipped_entries.is_empty() {
+            return;
+        }
+
+        println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+        println!(
+            "{}",
+            colorize_txt(
+                TextColor::BrightYellow,
+                &format!(
+                    "({}) Paths that were skipped while walking the directory:",
+                    self.skipped_entries.len()
+                )
+            )
+        );
+        println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+
+        for skipped in self.skipped_entries.iter() {
+            println!("{} ({})", skipped.path.display(), skipped.reason.as_str());
+        }
+    }
+
+    fn exclude_secondary_entries(&mut self, filter: &PathFilter) {
+        let entry_count =
+            self.secondary_ds.len() + self.secondary_symlinks.len() + self.secondary_dirs.len();
+
+        self.secondary_ds
+            .retain(|file| !filter.is_excluded_with_parents(&file.path, false));
+        self.secondary_symlinks
+            .retain(|symlink| !filter.is_excluded_with_parents(&symlink.path, false));
+        self.secondary_dirs
+            .retain(|dir| !filter.is_excluded_with_parents(dir, true));
+
+        self.excluded_entries_count = entry_count
+            - self.secondary_ds.len()
+            - self.secondary_symlinks.len()
+            - self.secondary_dirs.len();
+    }
+
+    fn secondary_symlink_targets(&self) -> HashMap<&Path, &Path> {
+        self.secondary_symlinks
+            .iter()
+            .map(|symlink| (symlink.path.as_path(), symlink.target.as_path()))
+            .collect()
+    }
+
+    /// Compares the links on disk (primary) with the ones in the digest (secondary)
+    fn find_symlink_changes(&mut self) {
+        let secondary_targets = self.secondary_symlink_targets();
+
+        let mut retargeted = Vec::new();
+        let mut dangling = Vec::new();
+        for (index, symlink) in self.primary_symlinks.iter().enumerate() {
+            if let Some(&target) = secondary_targets.get(symlink.path.as_path()) {
+                if target != symlink.target {
+                    retargeted.push(index);
+                }
+            }
+            if symlink.is_dangling() {
+                dangling.push(index);
+            }
+        }
+
+        self.retargeted_symlinks_index = retargeted;
+        self.dangling_symlinks_index = dangling;
+    }
+
+    fn print_log_symlink_changes(&self) {
+        let secondary_targets = self.secondary_symlink_targets();
+
+        if !self.retargeted_symlinks_index.is_empty() {
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::BrightYellow,
+                    &format!(
+                        "({}) Links that point somewhere else:",
+                        self.retargeted_symlinks_index.len()
+                    )
+                )
+            );
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+
+            for index in self.retargeted_symlinks_index.iter() {
+                let symlink = &self.primary_symlinks[*index];
+                let old_target = secondary_targets
+                    .get(symlink.path.as_path())
+                    .expect("BUG: Retargeted link is not in the digest");
+                println!(
+                    "{} ({} -> {})",
+                    symlink.path.display(),
+                    old_target.display(),
+                    symlink.target.display()
+                );
+            }
+        }
+
+        if !self.dangling_symlinks_index.is_empty() {
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::BrightYellow,
+                    &format!(
+                        "({}) Links whose target doesn't exist:",
+                        self.dangling_symlinks_index.len()
+                    )
+                )
+            );
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+
+            for index in self.dangling_symlinks_index.iter() {
+                let symlink = &self.primary_symlinks[*index];
+                println!("{} -> {}", symlink.path.display(), symlink.target.display());
+            }
+        }
+    }
+
+    /// Compares the mode and ownership of the files on disk (primary) with the digest (secondary),
+    /// for files whose content didn't change
+    fn find_unix_metadata_changes(&mut self, in_both_index: &[(usize, usize)]) {
+        let content_changed: HashSet<usize> = self
+            .invalid_hash_index
+            .iter()
+            .chain(&self.hashing_error_index)
+            .chain(&self.dirty_potentially_invalid_s_files_index)
+            .chain(&self.dirty_potentially_invalid_d_files_index)
+            .chain(&self.dirty_potentially_invalid_sd_files_index)
+            .chain(&self.metadata_unavailable_index)
+            .copied()
+            .collect();
+
+        for (primary_index, secondary_index) in in_both_index.iter() {
+            if content_changed.contains(primary_index) {
+                continue;
+            }
+
+            let on_disk = unix_metadata_of(&self.primary_ds[*primary_index]);
+            let in_digest = unix_metadata_of(&self.secondary_ds[*secondary_index]);
+
+            if let (Some(on_disk), Some(in_digest)) = (on_disk, in_digest) {
+                if !on_disk.same_permissions(in_digest) {
+                    self.permissions_changed_index.push(*primary_index);
+                }
+                if !on_disk.same_ownership(in_digest) {
+                    self.ownership_changed_index.push(*primary_index);
+                }
+            }
+        }
+    }
+
+    /// Like find_unix_metadata_changes, for validation where only the digest is loaded
+    fn find_unix_metadata_changes_on_disk(&mut self) {
+        let content_changed: HashSet<usize> = self
+            .invalid_hash_index
+            .iter()
+            .chain(&self.hashing_error_index)
+            .copied()
+            .collect();
+
+        for (index, file) in self.primary_ds.iter().enumerate() {
+            if content_changed.contains(&index) {
+                continue;
+            }
+
+            let in_digest = match unix_metadata_of(file) {
+                Some(unix) => unix,
+                None => continue,
+            };
+
+            //The file was just hashed, if it is gone since then there is nothing to compare
+            let on_disk = match file.path.metadata() {
+                Ok(metadata) => match UnixMetadata::from_fs(&metadata) {
+                    Some(unix) => unix,
+                    None => continue,
+                },
+                Err(_) => continue,
+            };
+
+            if !on_disk.same_permissions(in_digest) {
+                self.permissions_changed_index.push(index);
+            }
+            if !on_disk.same_ownership(in_digest) {
+                self.ownership_changed_index.push(index);
+            }
+        }
+    }
+
+    /// Compares the directories on disk (primary) with the ones in the digest (secondary). Digests
+    /// that don't record directories have none on either side.
+    fn find_dir_changes(&mut self) {
+        let primary_dirs: HashSet<&PathBuf> = self.primary_dirs.iter().collect();
+        let secondary_dirs: HashSet<&PathBuf> = self.secondary_dirs.iter().collect();
+
+        self.added_dirs_index = (0..self.primary_dirs.len())
+            .filter(|index| !secondary_dirs.contains(&self.primary_dirs[*index]))
+            .collect();
+        self.removed_dirs_index = (0..self.secondary_dirs.len())
+            .filter(|index| !primary_dirs.contains(&self.secondary_dirs[*index]))
+            .collect();
+    }
+
+    fn print_log_dir_changes(&self) {
+        for (indexes, dirs, title) in [
+            (
+                &self.added_dirs_index,
+                &self.primary_dirs,
+                "Directories that were added:",
+            ),
+            (
+                &self.removed_dirs_index,
+                &self.secondary_dirs,
+                "Directories that were removed:",
+            ),
+        ] {
+            if indexes.is_empty() {
+                continue;
+            }
+
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::BrightYellow,
+                    &format!("({}) {}", indexes.len(), title)
+                )
+            );
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+
+            for index in indexes.iter() {
+                println!("{}", dirs[*index].display());
+            }
+        }
+    }
+
+    /// Finds the files that are no longer hardlinks of the rest of their link group in the digest
+    /// (secondary). The links that still share the file most of the group is on are not reported.
+    fn find_broken_hardlinks(&mut self, in_both_index: &[(usize, usize)]) {
+        let mut groups: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (primary_index, secondary_index) in in_both_index.iter() {
+            let link_group = self.secondary_ds[*secondary_index]
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.link_group);
+            if let Some(link_group) = link_group {
+                groups.entry(link_group).or_default().push(*primary_index);
+            }
+        }
+
+        let mut broken = Vec::new();
+        for primary_indexes in groups.into_values() {
+            //files without an id (on platforms without inodes) can't be told apart
+            let mut file_ids: Vec<(usize, FileId)> = primary_indexes
+                .into_iter()
+                .filter_map(|index| Some((index, file_id_of(&self.primary_ds[index])?)))
+                .collect();
+            file_ids.sort_unstable_by_key(|(index, _)| *index);
+
+            let mut file_counts: HashMap<FileId, usize> = HashMap::new();
+            for (_, file_id) in file_ids.iter() {
+                *file_counts.entry(*file_id).or_default() += 1;
+            }
+            if file_counts.len() < 2 {
+                continue;
+            }
+
+            //on a tie, the file the first link is on is kept
+            let mut kept = file_ids[0].1;
+            for (_, file_id) in file_ids.iter() {
+                if file_counts[file_id] > file_counts[&kept] {
+                    kept = *file_id;
+                }
+            }
+            broken.extend(
+                file_ids
+                    .into_iter()
+                    .filter(|(_, file_id)| *file_id != kept)
+                    .map(|(index, _)| index),
+            );
+        }
+
+        broken.sort_unstable();
+        self.broken_hardlinks_index = broken;
+    }
+
+    fn print_log_broken_hardlinks(&self) {
+        if self.broken_hardlinks_index.is_empty() {
+            return;
+        }
+
+        println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+        println!(
+            "{}",
+            colorize_txt(
+                TextColor::BrightYellow,
+                &format!(
+                    "({}) Files that are no longer hardlinks of the other files of their link group:",
+                    self.broken_hardlinks_index.len()
+                )
+            )
+        );
+        println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+
+        for index in self.broken_hardlinks_index.iter() {
+            println!("{}", self.primary_ds[*index].path.display());
+        }
+    }
+
+    fn print_log_unix_metadata_changes(&self) {
+        for (indexes, title) in [
+            (
+                &self.permissions_changed_index,
+                "Files with intact content, but changed permissions:",
+            ),
+            (
+                &self.ownership_changed_index,
+                "Files with intact content, but changed owner or group:",
+            ),
+        ] {
+            if indexes.is_empty() {
+                continue;
+            }
+
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::BrightYellow,
+                    &format!("({}) {}", indexes.len(), title)
+                )
+            );
+            println!("{}", colorize_txt(TextColor::BrightYellow, "######"));
+
+            for index in indexes.iter() {
+                println!("{}", self.primary_ds[*index].path.display());
+            }
+        }
+    }
+
+    /// Header of a digest saved in the current mode
+    fn new_digest_header(&self) -> DigestHeader {
+        let mode = match self.mode {
+            Some(Mode::Generate) => DigestMode::Generate,
+            Some(Mode::Validate) => DigestMode::Validate,
+            Some(Mode::FastRefresh) => DigestMode::FastRefresh,
+            Some(Mode::FullRefresh) => DigestMode::FullRefresh,
+            //Converting a digest doesn't change what produced it
+            Some(Mode::Export) | Some(Mode::FindDuplicates) => return self.loaded_header.clone(),
+            //A migration target has no mode of its own, it is only filled by start_migrate
+            Some(Mode::Migrate) | None => DigestMode::Migrate,
+        };
+
+        DigestHeader::new(
+            &self.base_path,
+            mode,
+            self.symlink_policy,
+            self.ignore_patterns.clone(),
+            self.one_file_system,
+            self.record_dirs,
+            self.record_link_groups,
+        )
+    }
+}
+
+fn unix_metadata_of<H: HashValue>(file: &FileSt<H>) -> Option<&UnixMetadata> {
+    file.metadata
+        .as_ref()
+        .and_then(|metadata| metadata.unix.as_ref())
+}
+
+fn has_unix_metadata<H: HashValue>(files: &[FileSt<H>]) -> bool {
+    files.iter().any(|file| unix_metadata_of(file).is_some())
+}
+
+/// Numbers the groups of files that are hardlinks of each other, in the order of the files.
+/// Files whose other links are outside of the walked tree get no group, and neither do followed
+/// symlinks.
+fn assign_link_groups<H: HashValue>(files: &mut [FileSt<H>]) {
+    let hardlink_id = |metadata: &FileMetadata| {
+        metadata
+            .file_id
+            .filter(|_| metadata.link_count.is_some_and(|count| count > 1))
+    };
+
+    let mut link_counts: HashMap<FileId, usize> = HashMap::new();
+    for file_id in files
+        .iter()
+        .filter_map(|file| file.metadata.as_ref().and_then(hardlink_id))
+    {
+        *link_counts.entry(file_id).or_default() += 1;
+    }
+
+    let mut groups: HashMap<FileId, u64> = HashMap::new();
+    for metadata in files.iter_mut().filter_map(|file| file.metadata.as_mut()) {
+        metadata.link_group = match hardlink_id(metadata) {
+            Some(file_id) if link_counts[&file_id] > 1 => {
+                let next_group = groups.len() as u64 + 1;
+                Some(*groups.entry(file_id).or_insert(next_group))
+            }
+            _ => None,
+        };
+    }
+}
+
+fn file_id_of<H: HashValue>(file: &FileSt<H>) -> Option<FileId> {
+    file.metadata.as_ref().and_then(|metadata| metadata.file_id)
+}
+
+fn strip_unix_metadata<H: HashValue>(files: &mut [FileSt<H>]) {
+    for metadata in files.iter_mut().filter_map(|file| file.metadata.as_mut()) {
+        metadata.unix = None;
+    }
+}
+
+/// A digest file entry that is hashed with both its own hash type and the migration target's
+struct MigrationJob<'a, H: HashValue> {
+    file: &'a mut FileSt<H>,
+    target_state: Option<Box<dyn AnyHashState>>,
+    target_parallel: bool,
+}
+
+impl<H: HashValue + Send> HashJob for MigrationJob<'_, H> {
+    fn size(&self) -> u64 {
+        self.file.size()
+    }
+
+    fn calc_hash(&mut self) -> io::Result<()> {
+        let target_state = self
+            .target_state
+            .as_mut()
+            .expect("BUG: In migration, file has no target hash state");
+
+        let mut state = H::new_state();
+        let mut buffer = vec![0; FILE_BUFFER_SIZE];
+        read_file_chunks(&self.file.path, &mut buffer, |chunk| {
+            state.update(chunk);
+            target_state.update(chunk);
+        })?;
+
+        self.file.calculated_hash = Some(H::from_state(state));
+        Ok(())
+    }
+
+    //Same as HashValue::new_hash_large_file, with both states fed the big chunks
+    fn calc_hash_large(&mut self) -> io::Result<()> {
+        if !self.parallel_large_file_hashing() {
+            return self.calc_hash();
+        }
+
+        let target_state = self
+            .target_state
+            .as_mut()
+            .expect("BUG: In migration, file has no target hash state");
+
+        let mut state = H::new_state();
+        let mut buffer = vec![0; LARGE_FILE_BUFFER_SIZE];
+        read_file_chunks(&self.file.path, &mut buffer, |chunk| {
+            state.update_large(chunk);
+            target_state.update_large(chunk);
+        })?;
+
+        self.file.calculated_hash = Some(H::from_state(state));
+        Ok(())
+    }
+
+    fn parallel_large_file_hashing(&self) -> bool {
+        H::parallel_large_file_hashing() || self.target_parallel
+    }
+
+    //Digest entries have no file id, so every job is hashed
+    fn file_id(&self) -> Option<FileId> {
+        None
+    }
+
+    fn copy_hash_from(&mut self, _other: &Self) {
+        unreachable!("BUG: In migration, a job without a file id was not hashed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_rep::hash::md5::HashMD5;
+
+    fn digest_entry(path: &str) -> FileSt<HashMD5> {
+        FileSt::new(
+            PathBuf::from(path),
+            HashMD5::new_from_string("4534bfadb395bc299157d52eac16c368"),
+            None,
+        )
+    }
+
+    fn paths(files: &[FileSt<HashMD5>]) -> Vec<&Path> {
+        files.iter().map(|file| file.path.as_path()).collect()
+    }
+
+    //A directory whose listing failed part way: what was listed is compared as usual, only the
+    //rest is kept from the digest
+    #[test]
+    fn keep_unwalked_entries_of_partly_listed_dir() {
+        let mut engine = Engine::<HashMD5>::new();
+        engine.primary_ds = vec![FileSt::new(PathBuf::from("/base/dir/a"), None, None)];
+        engine.primary_dirs = vec![PathBuf::from("/base/dir")];
+        engine.secondary_ds = vec![
+            digest_entry("/base/dir/a"),
+            digest_entry("/base/dir/b"),
+            digest_entry("/base/other"),
+        ];
+        engine.secondary_dirs = vec![PathBuf::from("/base/dir"), PathBuf::from("/base/dir/sub")];
+        engine.walk_errors = vec![WalkError {
+            path: PathBuf::from("/base/dir"),
+            error: io::Error::other("entry could not be read"),
+        }];
+
+        engine.keep_unwalked_entries();
+
+        assert_eq!(paths(&engine.unwalked_ds), [Path::new("/base/dir/b")]);
+        assert_eq!(
+            paths(&engine.secondary_ds),
+            [Path::new("/base/dir/a"), Path::new("/base/other")]
+        );
+        assert_eq!(
+            engine.primary_dirs,
+            [PathBuf::from("/base/dir"), PathBuf::from("/base/dir/sub")]
+        );
+        //kept as they were
+        assert!(engine.unwalked_ds[0].calculated_hash.is_some());
+    }
+
+    #[test]
+    fn keep_unwalked_entries_of_unreadable_dir() {
+        let mut engine = Engine::<HashMD5>::new();
+        engine.primary_ds = vec![FileSt::new(PathBuf::from("/base/other"), None, None)];
+        engine.secondary_ds = vec![digest_entry("/base/dir/a"), digest_entry("/base/other")];
+        engine.walk_errors = vec![WalkError {
+            path: PathBuf::from("/base/dir"),
+            error: io::Error::from(io::ErrorKind::PermissionDenied),
+        }];
+
+        engine.keep_unwalked_entries();
+
+        assert_eq!(paths(&engine.unwalked_ds), [Path::new("/base/dir/a")]);
+        assert_eq!(paths(&engine.secondary_ds), [Path::new("/base/other")]);
+    }
+
+    //A file that fails to hash during a refresh is reported as such, instead of compared.
+    //'/proc/self/mem' can't be read from its start, even by root
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn refresh_with_hashing_error() {
+        let dir = std::env::temp_dir().join(format!(
+            "sfisum-{}-refresh-hashing-error",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let base = dir.join("base");
+        fs::create_dir_all(&base).unwrap();
+        fs::write(base.join("a"), "content").unwrap();
+        std::os::unix::fs::symlink("/proc/self/mem", base.join("mem")).unwrap();
+        //no metadata, so a fast refresh hashes both
+        let dd_file_path = dir.join("digest.ddmd5");
+        fs::write(
+            &dd_file_path,
+            "; Hash: md5\n\
+             9a0364b9e99bb480dd25e1f0284c8555 *a\n\
+             9a0364b9e99bb480dd25e1f0284c8555 *mem\n",
+        )
+        .unwrap();
+
+        let refresh = |start: fn(&mut Engine<HashMD5>) -> Result<(), String>| {
+            let mut engine = Engine::<HashMD5>::new();
+            engine.set_paths(dd_file_path.clone(), base.clone());
+            start(&mut engine).unwrap();
+
+            let failed: Vec<&Path> = engine
+                .hashing_error_index
+                .iter()
+                .map(|index| engine.primary_ds[*index].path.as_path())
+                .collect();
+            assert_eq!(failed, [base.join("mem")]);
+            assert!(engine.invalid_hash_index.is_empty());
+            assert!(engine.metadata_unavailable_index.is_empty());
+            assert!(engine.crosscheck_primary_orphans_index.is_empty());
+            assert!(engine.crosscheck_secondary_orphans_index.is_empty());
+        };
+
+        refresh(Engine::start_fast_refresh);
+        refresh(Engine::start_full_refresh);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn link_groups(files: &[FileSt<HashMD5>]) -> Vec<Option<u64>> {
+        files
+            .iter()
+            .map(|file| file.metadata.as_ref().unwrap().link_group)
+            .collect()
+    }
+
+    //the files of a digest with a link group 1, and what is on disk by now
+    fn hardlink_engine(disk: Vec<FileSt<HashMD5>>) -> Engine<HashMD5> {
+        let mut engine = Engine::<HashMD5>::new();
+        engine.secondary_ds = disk
+            .iter()
+            .map(|file| {
+                let mut metadata = FileMetadata::new_secs(0, 0);
+                metadata.link_group = Some(1);
+                FileSt::new(file.path.clone(), None, Some(metadata))
+            })
+            .collect();
+        engine.primary_ds = disk;
+        engine
+    }
+
+    //A pair of hardlinks that became two copies has one link each left, which must not be taken
+    //for the same file
+    #[cfg(unix)]
+    #[test]
+    fn broken_hardlink_pair() {
+        let dir = std::env::temp_dir().join(format!(
+            "sfisum-{}-broken-hardlink-pair",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a"), "same").unwrap();
+        fs::hard_link(dir.join("a"), dir.join("b")).unwrap();
+        fs::write(dir.join("c"), "other").unwrap();
+        std::os::unix::fs::symlink("a", dir.join("d")).unwrap();
+
+        let walk = || {
+            let filter = PathFilter::new(dir.clone(), Vec::new()).unwrap();
+            let mut walker =
+                DirectoryWalker::<HashMD5>::new(dir.clone(), SymlinkPolicy::Follow, filter);
+            walker.walk().unwrap();
+            walker.into_files_and_symlinks().0
+        };
+
+        let mut digest = walk();
+        assign_link_groups(&mut digest);
+        assert_eq!(link_groups(&digest), [Some(1), Some(1), None, None]);
+
+        //still linked
+        let mut engine = Engine::<HashMD5>::new();
+        engine.secondary_ds = digest;
+        engine.primary_ds = walk();
+        engine.find_broken_hardlinks(&[(0, 0), (1, 1), (2, 2), (3, 3)]);
+        assert!(engine.broken_hardlinks_index.is_empty());
+
+        fs::remove_file(dir.join("b")).unwrap();
+        fs::write(dir.join("b"), "same").unwrap();
+
+        engine.primary_ds = walk();
+        engine.find_broken_hardlinks(&[(0, 0), (1, 1), (2, 2), (3, 3)]);
+        assert_eq!(engine.broken_hardlinks_index, [1]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn broken_hardlinks_of_majority() {
+        let file = |path: &str, inode: u64| {
+            let mut metadata = FileMetadata::new_secs(0, 0);
+            metadata.file_id = Some(FileId { device: 1, inode });
+            FileSt::new(PathBuf::from(path), None, Some(metadata))
+        };
+
+        //the file most of the group is still on is kept, even if it is not the first
+        let mut engine = hardlink_engine(vec![file("a", 1), file("b", 2), file("c", 2)]);
+        engine.find_broken_hardlinks(&[(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(engine.broken_hardlinks_index, [0]);
+
+        let mut engine = hardlink_engine(vec![file("a", 1), file("b", 1), file("c", 1)]);
+        engine.find_broken_hardlinks(&[(0, 0), (1, 1), (2, 2)]);
+        assert!(engine.broken_hardlinks_index.is_empty());
+    }
+
+    //without inodes, nothing can be told
+    #[test]
+    fn broken_hardlinks_without_file_ids() {
+        let file = |path: &str| {
+            FileSt::new(
+                PathBuf::from(path),
+                None,
+                Some(FileMetadata::new_secs(0, 0)),
+            )
+        };
+
+        let mut engine = hardlink_engine(vec![file("a"), file("b")]);
+        engine.find_broken_hardlinks(&[(0, 0), (1, 1)]);
+        assert!(engine.broken_hardlinks_index.is_empty());
+    }
+}
@@ -1,20 +1,258 @@
+use crate::engine::dd_compression::{GZIP_SUFFIX, ZSTD_SUFFIX};
+use crate::engine::dd_file_rw::{sum_suffix_of_path, BINARY_SUFFIX, JSON_SUFFIX, SFV_SUFFIX};
 use crate::engine::engine::{Engine, EngineAny};
+use crate::file_rep::hash::blake3::HashBLAKE3;
+use crate::file_rep::hash::crc32::HashCRC32;
 use crate::file_rep::hash::md5::HashMD5;
-use crate::file_rep::hash_def::{hash_type_suffix_parse, HashType};
+use crate::file_rep::hash::pair::{HashPair, PairNames};
+use crate::file_rep::hash::sha256::HashSHA256;
+use crate::file_rep::hash::xxh3::HashXXH3;
+use crate::file_rep::hash_def::HashValue;
+use lazy_static::lazy_static;
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::RwLock;
 
-pub fn create_engine(hash_type: HashType) -> Box<dyn EngineAny> {
-    match hash_type {
-        HashType::MD5 => Box::new(Engine::<HashMD5>::new(HashType::MD5)),
+/// A hash algorithm that engines can be created for.
+/// Built from a HashValue implementation, so anything embedding sfisum can register its own.
+#[derive(Clone, Copy)]
+pub struct HashAlgorithm {
+    pub name: &'static str,
+    pub dd_suffix: &'static str,
+    pub sum_suffixes: &'static [&'static str],
+    pub sfv_compatible: bool,
+    constructor: fn() -> Box<dyn EngineAny>,
+}
+
+impl HashAlgorithm {
+    pub fn of<H>() -> Self
+    where
+        H: HashValue + Send + Sync + Debug + 'static,
+    {
+        HashAlgorithm {
+            name: H::signature_to_string(),
+            dd_suffix: H::dd_suffix(),
+            sum_suffixes: H::sum_suffixes(),
+            sfv_compatible: H::sfv_compatible(),
+            constructor: construct_engine::<H>,
+        }
+    }
+
+    pub fn create_engine(&self) -> Box<dyn EngineAny> {
+        (self.constructor)()
+    }
+}
+
+fn construct_engine<H>() -> Box<dyn EngineAny>
+where
+    H: HashValue + Send + Sync + Debug + 'static,
+{
+    Box::new(Engine::<H>::new())
+}
+
+//PairNames of the first algorithm paired with each of the others, then of the second one with each
+//of the ones after it, and so on. An algorithm is (type, name, dd suffix without 'dd', [sum suffixes])
+macro_rules! impl_pair_names {
+    () => {};
+    (($a:ty, $a_name:literal, $a_dd:literal, $a_sums:tt)
+        $(, ($b:ty, $b_name:literal, $b_dd:literal, $b_sums:tt))*) => {
+        $(impl PairNames for HashPair<$a, $b> {
+            const NAME: &'static str = concat!($a_name, "+", $b_name);
+            const DD_SUFFIX: &'static str = concat!("dd", $a_dd, $b_dd);
+            const SUM_SUFFIXES: &'static [&'static str] = concat_sum_suffixes!($a_sums, $b_sums);
+        })*
+        impl_pair_names!($(($b, $b_name, $b_dd, $b_sums)),*);
+    };
+}
+
+macro_rules! concat_sum_suffixes {
+    ([$($a:literal),*], [$($b:literal),*]) => {
+        &[$($a,)* $($b),*]
+    };
+}
+
+impl_pair_names!(
+    (HashMD5, "md5", "md5", ["md5"]),
+    (HashSHA256, "sha256", "sha256", ["sha256"]),
+    (HashBLAKE3, "blake3", "blake3", ["b3"]),
+    (HashXXH3, "xxh3_128", "xxh3", ["xxh128"]),
+    (HashCRC32, "crc32", "crc32", [])
+);
+
+//Registers a HashPair of the first type with each of the others, then of the second type with each
+//of the ones after it, and so on
+macro_rules! push_hash_pairs {
+    ($algorithms:ident;) => {};
+    ($algorithms:ident; $first:ty $(, $rest:ty)*) => {
+        $($algorithms.push(HashAlgorithm::of::<HashPair<$first, $rest>>());)*
+        push_hash_pairs!($algorithms; $($rest),*);
+    };
+}
+
+lazy_static! {
+    static ref HASH_ALGORITHMS: RwLock<Vec<HashAlgorithm>> = RwLock::new({
+        let mut algorithms = vec![
+            HashAlgorithm::of::<HashMD5>(),
+            HashAlgorithm::of::<HashSHA256>(),
+            HashAlgorithm::of::<HashBLAKE3>(),
+            HashAlgorithm::of::<HashXXH3>(),
+            HashAlgorithm::of::<HashCRC32>(),
+        ];
+        //'md5+sha256', 'sha256+blake3', ... - both hashed in a single read pass
+        push_hash_pairs!(algorithms; HashMD5, HashSHA256, HashBLAKE3, HashXXH3, HashCRC32);
+        algorithms
+    });
+}
+
+/// Adds a hash algorithm to the registry. Names and DD suffixes must be unique.
+pub fn register_hash_algorithm(algorithm: HashAlgorithm) -> Result<(), String> {
+    let mut algorithms = HASH_ALGORITHMS
+        .write()
+        .expect("BUG: Hash algorithm registry is poisoned");
+
+    if algorithms
+        .iter()
+        .any(|a| a.name == algorithm.name || a.dd_suffix == algorithm.dd_suffix)
+    {
+        return Err(format!(
+            "Hash algorithm '{}' (suffix '{}') is already registered",
+            algorithm.name, algorithm.dd_suffix
+        ));
     }
+
+    algorithms.push(algorithm);
+    Ok(())
+}
+
+pub fn hash_algorithms() -> Vec<HashAlgorithm> {
+    HASH_ALGORITHMS
+        .read()
+        .expect("BUG: Hash algorithm registry is poisoned")
+        .clone()
+}
+
+/// Combined algorithms are found with their parts in any order, 'sha256+md5' finds 'md5+sha256'
+pub fn hash_algorithm_by_name<S: AsRef<str>>(name: S) -> Option<HashAlgorithm> {
+    let name = name.as_ref();
+    let algorithms = hash_algorithms();
+
+    algorithms
+        .iter()
+        .find(|a| a.name == name)
+        .or_else(|| algorithms.iter().find(|a| same_parts(a.name, name)))
+        .copied()
+}
+
+fn same_parts(a: &str, b: &str) -> bool {
+    let mut a_parts: Vec<&str> = a.split('+').collect();
+    let mut b_parts: Vec<&str> = b.split('+').collect();
+    a_parts.sort_unstable();
+    b_parts.sort_unstable();
+
+    a_parts == b_parts
+}
+
+/// Names of the algorithms that are not a combination of others
+pub fn single_hash_algorithm_names() -> Vec<&'static str> {
+    hash_algorithms()
+        .iter()
+        .map(|a| a.name)
+        .filter(|name| !name.contains('+'))
+        .collect()
+}
+
+/// Like hash_algorithm_by_name, but the error lists every supported algorithm
+pub fn parse_hash_algorithm<S: AsRef<str>>(name: S) -> Result<HashAlgorithm, String> {
+    let name = name.as_ref();
+
+    hash_algorithm_by_name(name).ok_or_else(|| {
+        format!(
+            "Unsupported hash type '{}'. Supported hash types: {}, or two of them joined by '+'",
+            name,
+            single_hash_algorithm_names().join(", ")
+        )
+    })
+}
+
+pub fn hash_algorithm_by_suffix<S: AsRef<str>>(suffix: S) -> Option<HashAlgorithm> {
+    hash_algorithms()
+        .into_iter()
+        .find(|a| a.dd_suffix == suffix.as_ref())
+}
+
+/// The algorithm whose md5sum style checksum files use this suffix. Multi-hash algorithms are
+/// never matched, a checksum file only holds a single hash.
+pub fn hash_algorithm_by_sum_suffix<S: AsRef<str>>(suffix: S) -> Option<HashAlgorithm> {
+    hash_algorithms()
+        .into_iter()
+        .find(|a| a.sum_suffixes == [suffix.as_ref()])
 }
 
-pub fn dd_filename_to_hash_type<S: AsRef<str>>(filename: S) -> Option<HashType> {
+pub fn dd_filename_to_hash_type<S: AsRef<str>>(filename: S) -> Option<HashAlgorithm> {
     let filename = filename.as_ref();
-    let split: Vec<&str> = filename.split('.').collect();
-    let hash_type_str = split.last().unwrap();
+    let mut extensions = filename.rsplit('.');
+    let mut hash_type_str = extensions.next()?;
+
+    //'x.ddmd5.zst' - compression is the outermost extension
+    if [GZIP_SUFFIX, ZSTD_SUFFIX]
+        .iter()
+        .any(|suffix| hash_type_str.eq_ignore_ascii_case(suffix))
+    {
+        hash_type_str = extensions.next()?;
+    }
+
+    //'x.ddmd5.jsonl' - the hash type is the inner extension
+    if [JSON_SUFFIX, BINARY_SUFFIX]
+        .iter()
+        .any(|suffix| hash_type_str.eq_ignore_ascii_case(suffix))
+    {
+        hash_type_str = extensions.next()?;
+    }
+
+    //SFV files always hold CRC32 checksums
+    if hash_type_str.eq_ignore_ascii_case(SFV_SUFFIX) {
+        return hash_algorithms().into_iter().find(|a| a.sfv_compatible);
+    }
+
+    hash_algorithm_by_suffix(hash_type_str).or_else(|| {
+        //md5sum style checksum files: 'x.md5', 'MD5SUMS'
+        let sum_suffix = sum_suffix_of_path(Path::new(filename))?;
+        hash_algorithm_by_sum_suffix(sum_suffix)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_pair_names<A: HashValue, B: HashValue>()
+    where
+        HashPair<A, B>: HashValue,
+    {
+        assert_eq!(
+            HashPair::<A, B>::signature_to_string(),
+            format!("{}+{}", A::signature_to_string(), B::signature_to_string())
+        );
+        assert_eq!(
+            HashPair::<A, B>::dd_suffix(),
+            format!("{}{}", A::dd_suffix(), &B::dd_suffix()["dd".len()..])
+        );
+        assert_eq!(
+            HashPair::<A, B>::sum_suffixes(),
+            [A::sum_suffixes(), B::sum_suffixes()].concat()
+        );
+        assert_eq!(
+            HashPair::<A, B>::parallel_large_file_hashing(),
+            A::parallel_large_file_hashing() || B::parallel_large_file_hashing()
+        );
+    }
 
-    match hash_type_suffix_parse(hash_type_str) {
-        Some(hash_type) => Some(hash_type),
-        None => None,
+    #[test]
+    fn pair_names_match_their_parts() {
+        check_pair_names::<HashMD5, HashSHA256>();
+        check_pair_names::<HashMD5, HashBLAKE3>();
+        check_pair_names::<HashSHA256, HashXXH3>();
+        check_pair_names::<HashBLAKE3, HashCRC32>();
+        check_pair_names::<HashXXH3, HashCRC32>();
     }
 }
@@ -1,9 +1,12 @@
+use crate::file_rep::file_metadata::FileId;
 use crate::file_rep::file_st::FileSt;
 use crate::file_rep::hash_def::HashValue;
 use crate::util::console_text_formatter::{colorize_txt, TextColor};
 use lazy_static::lazy_static;
 use rayon::prelude::*;
 use rayon::ThreadPoolBuilder;
+use std::collections::HashMap;
+use std::io;
 use std::io::{stderr, Write};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
@@ -48,7 +51,56 @@ impl ProgressTracker {
     }
 }
 
-pub fn calculate_hashes<'a, H, I>(
+/// Anything the planner can hash - usually a single file
+pub trait HashJob: Send {
+    fn size(&self) -> u64;
+    fn calc_hash(&mut self) -> io::Result<()>;
+
+    //for jobs the planner found larger than the size threshold
+    fn calc_hash_large(&mut self) -> io::Result<()> {
+        self.calc_hash()
+    }
+
+    //see HashValue::parallel_large_file_hashing. Asked per job, as a migration only knows the
+    //target hash type at runtime
+    fn parallel_large_file_hashing(&self) -> bool;
+
+    /// Jobs with the same id (hardlinks of one file) are hashed once, see copy_hash_from.
+    /// Digest entries have no id, so those are hashed once per link.
+    fn file_id(&self) -> Option<FileId>;
+
+    /// Takes the result of the job with the same file id that was hashed instead
+    fn copy_hash_from(&mut self, other: &Self);
+}
+
+impl<H: HashValue + Send> HashJob for FileSt<H> {
+    fn size(&self) -> u64 {
+        FileSt::size(self)
+    }
+
+    fn calc_hash(&mut self) -> io::Result<()> {
+        FileSt::calc_hash(self)
+    }
+
+    fn calc_hash_large(&mut self) -> io::Result<()> {
+        self.calculated_hash = Some(H::new_hash_large_file(&self.path)?);
+        Ok(())
+    }
+
+    fn parallel_large_file_hashing(&self) -> bool {
+        H::parallel_large_file_hashing()
+    }
+
+    fn file_id(&self) -> Option<FileId> {
+        self.metadata.as_ref().and_then(|metadata| metadata.file_id)
+    }
+
+    fn copy_hash_from(&mut self, other: &Self) {
+        self.calculated_hash = other.calculated_hash.clone();
+    }
+}
+
+pub fn calculate_hashes<'a, J, I>(
     files_iter: I,
     small_threads: usize,
     large_threads: usize,
@@ -56,8 +108,8 @@ pub fn calculate_hashes<'a, H, I>(
     indexes_to_hash: Option<Vec<usize>>,
 ) -> Result<Option<Vec<usize>>, String>
 where
-    H: HashValue + Send + Sync + 'a,
-    I: IntoIterator<Item = &'a mut FileSt<H>>,
+    J: HashJob + 'a,
+    I: IntoIterator<Item = &'a mut J>,
 {
     let interrupt_flag = INTERRUPT_FLAG.clone();
 
@@ -66,11 +118,6 @@ where
         .build()
         .map_err(|e| e.to_string())?;
 
-    let large_pool = ThreadPoolBuilder::new()
-        .num_threads(large_threads)
-        .build()
-        .map_err(|e| e.to_string())?;
-
     let files: Vec<_> = files_iter.into_iter().collect();
 
     let indexes_set = indexes_to_hash.map(|indexes| {
@@ -84,6 +131,10 @@ where
     let mut small_indexes = Vec::new();
     let mut large_indexes = Vec::new();
 
+    //Further hardlinks of a file that is hashed already, with the index of that file
+    let mut first_links: HashMap<FileId, usize> = HashMap::new();
+    let mut other_links = Vec::new();
+
     for (index, file) in files.into_iter().enumerate() {
         if let Some(ref indexes) = indexes_set {
             if !indexes.contains(&index) {
@@ -91,7 +142,15 @@ where
             }
         }
 
-        if file.metadata.size <= size_threshold {
+        if let Some(file_id) = file.file_id() {
+            if let Some(&first_index) = first_links.get(&file_id) {
+                other_links.push((index, first_index, file));
+                continue;
+            }
+            first_links.insert(file_id, index);
+        }
+
+        if file.size() <= size_threshold {
             small_indexes.push(index);
             small_files.push(file);
         } else {
@@ -100,6 +159,17 @@ where
         }
     }
 
+    //Hashers that split a single file over multiple threads get the whole machine, but are fed
+    //large files one at a time
+    let parallel_hasher = large_files
+        .iter()
+        .any(|file| file.parallel_large_file_hashing());
+
+    let large_pool = ThreadPoolBuilder::new()
+        .num_threads(if parallel_hasher { 0 } else { large_threads })
+        .build()
+        .map_err(|e| e.to_string())?;
+
     let total_small = small_files.len();
     let total_large = large_files.len();
 
@@ -125,7 +195,7 @@ where
                         return Err(());
                     }
 
-                    if let Err(_) = file.calc_hash() {
+                    if file.calc_hash().is_err() {
                         if let Ok(mut failed) = failed_indexes_clone.lock() {
                             failed.push(small_indexes[local_index]);
                         }
@@ -156,33 +226,67 @@ where
     let failed_indexes_clone = failed_indexes.clone();
     let interrupt_flag_clone = interrupt_flag.clone();
 
-    large_pool
-        .install(|| {
-            large_files
-                .par_iter_mut()
-                .enumerate()
-                .try_for_each(|(local_index, file)| {
-                    if interrupt_flag_clone.load(Ordering::SeqCst) {
-                        return Err(());
-                    }
+    let hash_large_file = |(local_index, file): (usize, &mut &mut J)| {
+        if interrupt_flag_clone.load(Ordering::SeqCst) {
+            return Err(());
+        }
 
-                    if let Err(_) = file.calc_hash() {
-                        if let Ok(mut failed) = failed_indexes_clone.lock() {
-                            failed.push(large_indexes[local_index]);
-                        }
-                    }
+        if file.calc_hash_large().is_err() {
+            if let Ok(mut failed) = failed_indexes_clone.lock() {
+                failed.push(large_indexes[local_index]);
+            }
+        }
 
-                    let remaining = remaining_large_clone.fetch_sub(1, Ordering::Relaxed);
-                    if let Ok(mut tracker) = tracker_clone.lock() {
-                        if tracker.should_update() {
-                            print_progress(remaining - 1, false);
-                        }
-                    }
-                    Ok(())
-                })
+        let remaining = remaining_large_clone.fetch_sub(1, Ordering::Relaxed);
+        if let Ok(mut tracker) = tracker_clone.lock() {
+            if tracker.should_update() {
+                print_progress(remaining - 1, false);
+            }
+        }
+        Ok(())
+    };
+
+    large_pool
+        .install(|| {
+            if parallel_hasher {
+                large_files
+                    .iter_mut()
+                    .enumerate()
+                    .try_for_each(hash_large_file)
+            } else {
+                large_files
+                    .par_iter_mut()
+                    .enumerate()
+                    .try_for_each(hash_large_file)
+            }
         })
         .map_err(|_| "Error hashing large files".to_string())?;
 
+    //Hardlinks get the result of the link that was hashed
+    if !other_links.is_empty() {
+        let hashed: HashMap<usize, &J> = small_indexes
+            .iter()
+            .copied()
+            .zip(small_files.iter().map(|file| &**file))
+            .chain(
+                large_indexes
+                    .iter()
+                    .copied()
+                    .zip(large_files.iter().map(|file| &**file)),
+            )
+            .collect();
+        let mut failed = failed_indexes.lock().unwrap();
+        let failed_first: std::collections::HashSet<usize> = failed.iter().copied().collect();
+
+        for (index, first_index, file) in other_links {
+            if failed_first.contains(&first_index) {
+                failed.push(index);
+                continue;
+            }
+            file.copy_hash_from(hashed[&first_index]);
+        }
+    }
+
     //Clear the progress line
     eprintln!("\r{}\r", " ".repeat(70));
 
@@ -207,7 +311,7 @@ fn print_progress(remaining: usize, is_small: bool) {
     let indicator = if is_small { "(S: 1/2)" } else { "(L: 2/2)" };
     let remaining_str = format!(
         "{} {} {} ",
-        colorize_txt(TextColor::BrightMagenta, &*remaining.to_string()),
+        colorize_txt(TextColor::BrightMagenta, &remaining.to_string()),
         colorize_txt(TextColor::BrightMagenta, "Files remaining"),
         colorize_txt(TextColor::BrightMagenta, indicator)
     );
@@ -1,4 +1,8 @@
-pub mod engine;
+pub mod dd_compression;
 pub mod dd_file_rw;
+pub mod dd_header;
+pub mod dd_signature;
+#[allow(clippy::module_inception)]
+pub mod engine;
 pub mod engine_factory;
 mod hash_calc_planner;
\ No newline at end of file
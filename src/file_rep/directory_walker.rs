@@ -1,25 +1,184 @@
-use crate::file_rep::file_metadata::FileMetadata;
-use crate::file_rep::file_st::FileSt;
+use crate::constants::WALK_THREADS;
+use crate::file_rep::file_metadata::{FileId, FileMetadata, UnixMetadata};
+use crate::file_rep::file_st::{FileSt, SymlinkSt};
 use crate::file_rep::hash_def::HashValue;
-use std::path::PathBuf;
+use crate::file_rep::path_filter::{DirFilter, PathFilter};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::{fs, io};
 
+/// What the walker does with symbolic links
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum SymlinkPolicy {
+    /// Links are left out of the digest
+    Skip,
+    /// Links are walked as the file or directory they point to. Directories are only walked once,
+    /// so links back into the tree don't recurse forever
+    #[default]
+    Follow,
+    /// Links are not followed, but recorded with their target
+    Record,
+}
+
+impl SymlinkPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SymlinkPolicy::Skip => "skip",
+            SymlinkPolicy::Follow => "follow",
+            SymlinkPolicy::Record => "record",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "skip" => Some(SymlinkPolicy::Skip),
+            "follow" => Some(SymlinkPolicy::Follow),
+            "record" => Some(SymlinkPolicy::Record),
+            _ => None,
+        }
+    }
+}
+
+/// A file or directory that couldn't be read while walking
+#[derive(Debug)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub error: io::Error,
+}
+
+/// Why the walker left out an entry it could read
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SkipReason {
+    /// A mount point, or a link to another filesystem, with one_file_system
+    OtherFilesystem,
+    /// A followed link that points nowhere
+    DanglingLink,
+    Fifo,
+    Socket,
+    /// Character or block device
+    Device,
+    /// Anything else that is neither a file nor a directory
+    Special,
+}
+
+impl SkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::OtherFilesystem => "on another filesystem",
+            SkipReason::DanglingLink => "link to nothing",
+            SkipReason::Fifo => "FIFO",
+            SkipReason::Socket => "socket",
+            SkipReason::Device => "device node",
+            SkipReason::Special => "special file",
+        }
+    }
+
+    #[cfg(unix)]
+    fn of_special_file(file_type: &fs::FileType) -> Self {
+        use std::os::unix::fs::FileTypeExt;
+
+        if file_type.is_fifo() {
+            SkipReason::Fifo
+        } else if file_type.is_socket() {
+            SkipReason::Socket
+        } else if file_type.is_char_device() || file_type.is_block_device() {
+            SkipReason::Device
+        } else {
+            SkipReason::Special
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn of_special_file(_file_type: &fs::FileType) -> Self {
+        SkipReason::Special
+    }
+}
+
+#[derive(Debug)]
+pub struct SkippedEntry {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// Walks a directory tree into a list of files, sorted by path.
+///
+/// The tree is walked one level at a time: the directories of a level are read in parallel, and
+/// their listings merged in order. The result is the same regardless of how many threads are used
+/// and which of them finishes first.
 pub struct DirectoryWalker<H>
 where
     H: HashValue,
 {
     pub base_path: PathBuf,
     pub files: Vec<FileSt<H>>,
+    pub symlink_policy: SymlinkPolicy,
+    /// Links found with SymlinkPolicy::Record
+    pub symlinks: Vec<SymlinkSt>,
+    /// Files and directories it leaves out
+    pub filter: PathFilter,
+    /// Whether paths that can't be read are collected in walk_errors and skipped, instead of
+    /// failing the whole walk. Off by default.
+    pub tolerant: bool,
+    pub walk_errors: Vec<WalkError>,
+    /// Whether directories on another filesystem than the base path (mount points) are skipped
+    /// instead of walked. Only unix has device IDs to tell, elsewhere it does nothing.
+    pub one_file_system: bool,
+    /// FIFOs, sockets, devices, and what one_file_system leaves out
+    pub skipped: Vec<SkippedEntry>,
+    /// Whether the walked directories are collected in dirs, so empty ones are not lost. Off by
+    /// default.
+    pub record_dirs: bool,
+    /// The directories below the base path, with record_dirs
+    pub dirs: Vec<PathBuf>,
+    //device ID of the base path, with one_file_system
+    base_device: Option<u64>,
+    //canonical paths of the walked directories, with SymlinkPolicy::Follow
+    visited_dirs: HashSet<PathBuf>,
+    //links to directories, walked after the real directories so those keep their own paths
+    pending_dir_links: Vec<PathBuf>,
+}
+
+//What reading a single directory found
+#[derive(Default)]
+struct DirListing {
+    files: Vec<(PathBuf, FileMetadata)>,
+    symlinks: Vec<SymlinkSt>,
+    //subdirectories, with their canonical path when links are followed
+    dirs: Vec<(PathBuf, Option<PathBuf>)>,
+    dir_links: Vec<PathBuf>,
+    skipped: Vec<SkippedEntry>,
+    errors: Vec<WalkError>,
+}
+
+//What the threads of the walker share
+struct WalkOptions<'a> {
+    filter: &'a PathFilter,
+    symlink_policy: SymlinkPolicy,
+    base_device: Option<u64>,
 }
 
 impl<H> DirectoryWalker<H>
 where
     H: HashValue,
 {
-    pub fn new(path: PathBuf) -> Self {
+    pub fn new(path: PathBuf, symlink_policy: SymlinkPolicy, filter: PathFilter) -> Self {
         DirectoryWalker {
             base_path: path,
             files: Vec::new(),
+            symlink_policy,
+            symlinks: Vec::new(),
+            filter,
+            tolerant: false,
+            walk_errors: Vec::new(),
+            one_file_system: false,
+            skipped: Vec::new(),
+            record_dirs: false,
+            dirs: Vec::new(),
+            base_device: None,
+            visited_dirs: HashSet::new(),
+            pending_dir_links: Vec::new(),
         }
     }
 
@@ -35,19 +194,43 @@ where
 
         //clear files
         self.files.clear();
+        self.symlinks.clear();
+        self.walk_errors.clear();
+        self.skipped.clear();
+        self.dirs.clear();
+        self.visited_dirs.clear();
+        self.pending_dir_links.clear();
+
+        self.base_device = match self.one_file_system {
+            true => device_id(&fs::metadata(&self.base_path)?),
+            false => None,
+        };
 
         let dir = self.base_path.clone();
+        if self.symlink_policy == SymlinkPolicy::Follow {
+            self.visited_dirs.insert(dir.canonicalize()?);
+        }
 
-        match self.walk_rec(&dir) {
-            Err(ioerror) => {
-                self.files.clear();
-                return Err(ioerror);
-            }
-            _ => {}
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(WALK_THREADS)
+            .build()
+            .map_err(io::Error::other)?;
+
+        if let Err(ioerror) = self.walk_levels(&pool, vec![dir]) {
+            self.files.clear();
+            self.symlinks.clear();
+            self.dirs.clear();
+            return Err(ioerror);
         }
 
-        //check if any files were found
-        match self.files.len() {
+        self.files.sort_by(|a, b| a.path.cmp(&b.path));
+        self.symlinks.sort_by(|a, b| a.path.cmp(&b.path));
+        self.walk_errors.sort_by(|a, b| a.path.cmp(&b.path));
+        self.skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        self.dirs.sort();
+
+        //check if any files were found, a tree of empty directories is fine if they are recorded
+        match self.files.len() + self.dirs.len() {
             0 => Err(io::Error::new(
                 io::ErrorKind::NotFound,
                 "No files found in directory",
@@ -56,27 +239,231 @@ where
         }
     }
 
-    fn walk_rec(&mut self, dir: &PathBuf) -> io::Result<()> {
-        if dir.is_dir() {
-            for entry in fs::read_dir(dir)? {
-                let entry = entry?;
-                let path = entry.path();
+    fn walk_levels(&mut self, pool: &rayon::ThreadPool, mut level: Vec<PathBuf>) -> io::Result<()> {
+        loop {
+            //Links to directories are walked once the real directories are done
+            if level.is_empty() {
+                level = self.take_dir_links()?;
+                if level.is_empty() {
+                    return Ok(());
+                }
+            }
+
+            if self.record_dirs {
+                let base_path = &self.base_path;
+                self.dirs
+                    .extend(level.iter().filter(|dir| *dir != base_path).cloned());
+            }
+
+            let options = WalkOptions {
+                filter: &self.filter,
+                symlink_policy: self.symlink_policy,
+                base_device: self.base_device,
+            };
+            let listings: Vec<DirListing> = pool.install(|| {
+                level
+                    .par_iter()
+                    .map(|dir| read_dir_listing(dir, &options))
+                    .collect()
+            });
 
-                if path.is_file() {
-                    let metadata = path.metadata()?;
-                    let metadata = FileMetadata::new(metadata.modified()?, metadata.len());
+            level = Vec::new();
+            for listing in listings {
+                for error in listing.errors {
+                    self.walk_error(error)?;
+                }
+
+                self.files.extend(
+                    listing
+                        .files
+                        .into_iter()
+                        .map(|(path, metadata)| FileSt::new(path, None, Some(metadata))),
+                );
+                self.symlinks.extend(listing.symlinks);
+                if self.record_dirs {
+                    //Mount points are not walked, but are directories of the tree still
+                    self.dirs.extend(
+                        listing
+                            .skipped
+                            .iter()
+                            .filter(|skipped| skipped.reason == SkipReason::OtherFilesystem)
+                            .filter(|skipped| {
+                                skipped.path.symlink_metadata().is_ok_and(|m| m.is_dir())
+                            })
+                            .map(|skipped| skipped.path.clone()),
+                    );
+                }
+                self.skipped.extend(listing.skipped);
+                self.pending_dir_links.extend(listing.dir_links);
 
-                    let file = FileSt::new(path, None, metadata);
-                    self.files.push(file);
-                } else if path.is_dir() {
-                    self.walk_rec(&path)?;
+                for (dir, canonical_dir) in listing.dirs {
+                    if let Some(canonical_dir) = canonical_dir {
+                        self.visited_dirs.insert(canonical_dir);
+                    }
+                    level.push(dir);
                 }
             }
         }
+    }
+
+    //The pending links to directories that are not walked yet
+    fn take_dir_links(&mut self) -> io::Result<Vec<PathBuf>> {
+        let mut dirs = Vec::new();
+        for path in std::mem::take(&mut self.pending_dir_links) {
+            //A followed link can lead to a directory that is already walked, eg. a parent
+            match path.canonicalize() {
+                Ok(canonical_path) => {
+                    if self.visited_dirs.insert(canonical_path) {
+                        dirs.push(path);
+                    }
+                }
+                Err(error) => self.walk_error(WalkError { path, error })?,
+            }
+        }
+        Ok(dirs)
+    }
+
+    //Fails the walk, or records the error and carries on when tolerant
+    fn walk_error(&mut self, walk_error: WalkError) -> io::Result<()> {
+        if !self.tolerant {
+            return Err(walk_error.error);
+        }
+
+        self.walk_errors.push(walk_error);
         Ok(())
     }
 
-    pub fn into_files(self) -> Vec<FileSt<H>> {
-        self.files
+    pub fn into_files_and_symlinks(self) -> (Vec<FileSt<H>>, Vec<SymlinkSt>) {
+        (self.files, self.symlinks)
     }
 }
+
+//Reads the entries of a single directory, on a thread of the walker
+fn read_dir_listing(dir: &Path, options: &WalkOptions) -> DirListing {
+    let mut listing = DirListing::default();
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) => {
+            listing.errors.push(WalkError {
+                path: dir.to_path_buf(),
+                error,
+            });
+            return listing;
+        }
+    };
+
+    let mut dir_entries = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(entry) => dir_entries.push(entry),
+            //An entry that can't be read has no path of its own
+            Err(error) => listing.errors.push(WalkError {
+                path: dir.to_path_buf(),
+                error,
+            }),
+        }
+    }
+    //read_dir order depends on the filesystem
+    dir_entries.sort_by_key(|entry| entry.file_name());
+
+    let filter = options.filter.for_dir(dir);
+    for entry in dir_entries {
+        if let Err(error) = read_dir_entry(&entry, options, &filter, &mut listing) {
+            listing.errors.push(WalkError {
+                path: entry.path(),
+                error,
+            });
+        }
+    }
+
+    listing
+}
+
+fn read_dir_entry(
+    entry: &fs::DirEntry,
+    options: &WalkOptions,
+    filter: &DirFilter,
+    listing: &mut DirListing,
+) -> io::Result<()> {
+    let path = entry.path();
+
+    let is_symlink = entry.file_type()?.is_symlink();
+    if is_symlink {
+        match options.symlink_policy {
+            SymlinkPolicy::Skip => return Ok(()),
+            SymlinkPolicy::Record => {
+                if !filter.is_excluded(&path, false) {
+                    let target = fs::read_link(&path)?;
+                    listing.symlinks.push(SymlinkSt::new(path, target));
+                }
+                return Ok(());
+            }
+            SymlinkPolicy::Follow => {}
+        }
+    }
+
+    //Followed links are read as what they point to
+    let fs_metadata = match path.metadata() {
+        Ok(fs_metadata) => fs_metadata,
+        Err(e) if is_symlink && e.kind() == io::ErrorKind::NotFound => {
+            listing.skipped.push(SkippedEntry {
+                path,
+                reason: SkipReason::DanglingLink,
+            });
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+
+    if filter.is_excluded(&path, fs_metadata.is_dir()) {
+        return Ok(());
+    }
+
+    if options.base_device.is_some() && device_id(&fs_metadata) != options.base_device {
+        listing.skipped.push(SkippedEntry {
+            path,
+            reason: SkipReason::OtherFilesystem,
+        });
+        return Ok(());
+    }
+
+    if fs_metadata.is_file() {
+        let mut metadata = FileMetadata::new(fs_metadata.modified()?, fs_metadata.len());
+        metadata.unix = UnixMetadata::from_fs(&fs_metadata);
+        metadata.file_id = FileId::from_fs(&fs_metadata);
+        //a followed link is not a hardlink of what it points to
+        metadata.link_count = match is_symlink {
+            true => None,
+            false => FileId::link_count(&fs_metadata),
+        };
+
+        listing.files.push((path, metadata));
+    } else if fs_metadata.is_dir() && is_symlink {
+        listing.dir_links.push(path);
+    } else if fs_metadata.is_dir() {
+        let canonical_path = match options.symlink_policy {
+            SymlinkPolicy::Follow => Some(path.canonicalize()?),
+            _ => None,
+        };
+        listing.dirs.push((path, canonical_path));
+    } else {
+        listing.skipped.push(SkippedEntry {
+            path,
+            reason: SkipReason::of_special_file(&fs_metadata.file_type()),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn device_id(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
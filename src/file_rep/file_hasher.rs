@@ -1,26 +1,41 @@
-use crate::constants::FILE_BUFFER_SIZE;
+use crate::file_rep::hash_def::HashState;
 use digest::generic_array::GenericArray;
 use digest::Digest;
 use std::fs::File;
 use std::io;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::Path;
 
-pub fn hash_file<D>(path: &PathBuf) -> io::Result<GenericArray<u8, D::OutputSize>>
+/// Hashing state of any RustCrypto digest
+#[derive(Default)]
+pub struct DigestState<D: Digest>(D);
+
+impl<D: Digest> DigestState<D> {
+    pub fn finalize(self) -> GenericArray<u8, D::OutputSize> {
+        self.0.finalize()
+    }
+}
+
+impl<D: Digest + Send> HashState for DigestState<D> {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(&mut self.0, data);
+    }
+}
+
+/// Reads the whole file through the given buffer, passing every filled part of it to `consume`
+pub fn read_file_chunks<F>(path: &Path, buffer: &mut [u8], mut consume: F) -> io::Result<()>
 where
-    D: Digest,
+    F: FnMut(&[u8]),
 {
-    let mut hasher = D::new();
     let mut file = File::open(path)?;
-    let mut buffer = [0; FILE_BUFFER_SIZE];
 
     loop {
-        let bytes_read = file.read(&mut buffer)?;
+        let bytes_read = file.read(buffer)?;
         if bytes_read == 0 {
             break;
         }
-        hasher.update(&buffer[..bytes_read]);
+        consume(&buffer[..bytes_read]);
     }
 
-    Ok(hasher.finalize())
+    Ok(())
 }
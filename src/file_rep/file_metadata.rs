@@ -1,62 +1,388 @@
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fmt, fs};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FileMetadata {
     pub last_modified: SystemTime,
     pub size: u64,
+    /// Whether last_modified has sub-second precision. Digests of older versions only hold
+    /// whole seconds.
+    pub precise_mtime: bool,
+    /// Only recorded on request, see UnixMetadata
+    pub unix: Option<UnixMetadata>,
+    /// Only known for files just walked, see FileId
+    pub file_id: Option<FileId>,
+    /// Number of hardlinks of the file, only known for files just walked, and not through a
+    /// followed symlink. Not stored in digests.
+    pub link_count: Option<u64>,
+    /// Files of a digest with the same link group are hardlinks of one file
+    pub link_group: Option<u64>,
+}
+
+/// Device and inode of a file, the same for all of its hardlinks. Not stored in digests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId {
+    pub device: u64,
+    pub inode: u64,
+}
+
+impl FileId {
+    /// None on platforms without inodes
+    #[cfg(unix)]
+    pub fn from_fs(metadata: &fs::Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        Some(FileId {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_fs(_metadata: &fs::Metadata) -> Option<Self> {
+        None
+    }
+
+    /// Number of hardlinks of a file, None on platforms without inodes
+    #[cfg(unix)]
+    pub fn link_count(metadata: &fs::Metadata) -> Option<u64> {
+        use std::os::unix::fs::MetadataExt;
+
+        Some(metadata.nlink())
+    }
+
+    #[cfg(not(unix))]
+    pub fn link_count(_metadata: &fs::Metadata) -> Option<u64> {
+        None
+    }
+}
+
+/// Permissions, ownership and inode change time of a file on unix
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnixMetadata {
+    /// st_mode - the file type and permission bits
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub changed: SystemTime,
+}
+
+impl UnixMetadata {
+    /// None on platforms without unix metadata
+    #[cfg(unix)]
+    pub fn from_fs(metadata: &fs::Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        Some(UnixMetadata {
+            mode: metadata.mode(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            changed: UNIX_EPOCH
+                + Duration::new(metadata.ctime().max(0) as u64, metadata.ctime_nsec() as u32),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_fs(_metadata: &fs::Metadata) -> Option<Self> {
+        None
+    }
+
+    pub fn same_permissions(&self, other: &UnixMetadata) -> bool {
+        self.mode == other.mode
+    }
+
+    pub fn same_ownership(&self, other: &UnixMetadata) -> bool {
+        self.uid == other.uid && self.gid == other.gid
+    }
 }
 
 impl FileMetadata {
     pub fn new(last_modified: SystemTime, size: u64) -> Self {
-        let duration = last_modified
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default();
-        let normalized_time =
-            std::time::UNIX_EPOCH + std::time::Duration::from_secs(duration.as_secs());
+        //Times before the epoch are not stored
+        let last_modified = match last_modified.duration_since(UNIX_EPOCH) {
+            Ok(_) => last_modified,
+            Err(_) => UNIX_EPOCH,
+        };
 
         FileMetadata {
-            last_modified: normalized_time,
+            last_modified,
             size,
+            precise_mtime: true,
+            unix: None,
+            file_id: None,
+            link_count: None,
+            link_group: None,
+        }
+    }
+
+    /// Metadata with a modification time in whole seconds, without sub-second precision
+    pub fn new_secs(last_modified_secs: u64, size: u64) -> Self {
+        FileMetadata {
+            last_modified: UNIX_EPOCH + Duration::from_secs(last_modified_secs),
+            size,
+            precise_mtime: false,
+            unix: None,
+            file_id: None,
+            link_count: None,
+            link_group: None,
+        }
+    }
+
+    pub fn last_modified_secs(&self) -> u64 {
+        self.last_modified
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+
+    /// Nanoseconds past last_modified_secs, None without sub-second precision
+    pub fn last_modified_nanos(&self) -> Option<u32> {
+        self.precise_mtime.then(|| {
+            self.last_modified
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .subsec_nanos()
+        })
+    }
+
+    /// Compares the modification times, in whole seconds if either side lacks sub-second precision
+    pub fn same_last_modified(&self, other: &FileMetadata) -> bool {
+        if self.precise_mtime && other.precise_mtime {
+            self.last_modified == other.last_modified
+        } else {
+            self.last_modified_secs() == other.last_modified_secs()
         }
     }
 
+    /// Parses 'Size: <bytes>, Last modified: <unix seconds>[.<fraction>]', optionally followed by
+    /// ', Mode: <octal>, Uid: <uid>, Gid: <gid>, Changed: <unix seconds>.<fraction>' and
+    /// ', Link: <link group>'.
+    /// Fields are 'Key: value' pairs, unknown fields (written by newer versions) are ignored.
     pub fn new_from_string<S: AsRef<str>>(input: S) -> Result<Self, &'static str> {
         let s = input.as_ref();
 
-        let parts: Vec<&str> = s.split(", ").collect();
-        if parts.len() != 2 {
-            return Err("Invalid metadata string format");
+        let mut size = None;
+        let mut last_modified = None;
+        let mut mode = None;
+        let mut uid = None;
+        let mut gid = None;
+        let mut changed = None;
+        let mut link_group = None;
+
+        for part in s.split(", ") {
+            let (key, value) = match part.split_once(": ") {
+                Some(field) => field,
+                None => return Err("Invalid metadata string format"),
+            };
+
+            match key {
+                "Size" => match value.parse() {
+                    Ok(value) => size = Some(value),
+                    Err(_) => return Err("Invalid size format"),
+                },
+                "Last modified" => match parse_timestamp(value) {
+                    Some(value) => last_modified = Some(value),
+                    None => return Err("Invalid timestamp format"),
+                },
+                "Mode" => match u32::from_str_radix(value, 8) {
+                    Ok(value) => mode = Some(value),
+                    Err(_) => return Err("Invalid mode format"),
+                },
+                "Uid" => match value.parse() {
+                    Ok(value) => uid = Some(value),
+                    Err(_) => return Err("Invalid uid format"),
+                },
+                "Gid" => match value.parse() {
+                    Ok(value) => gid = Some(value),
+                    Err(_) => return Err("Invalid gid format"),
+                },
+                "Changed" => match parse_timestamp(value) {
+                    Some((secs, nanos)) => {
+                        changed = Some(UNIX_EPOCH + Duration::new(secs, nanos.unwrap_or(0)))
+                    }
+                    None => return Err("Invalid change time format"),
+                },
+                "Link" => match value.parse() {
+                    Ok(value) => link_group = Some(value),
+                    Err(_) => return Err("Invalid link group format"),
+                },
+                _ => {}
+            }
         }
 
-        let size = match parts[0].strip_prefix("Size: ").and_then(|s| s.parse().ok()) {
-            Some(size) => size,
-            None => return Err("Invalid size format"),
+        let mut metadata = match (size, last_modified) {
+            (Some(size), Some((secs, None))) => FileMetadata::new_secs(secs, size),
+            (Some(size), Some((secs, Some(nanos)))) => {
+                FileMetadata::new(UNIX_EPOCH + Duration::new(secs, nanos), size)
+            }
+            _ => return Err("Missing size or timestamp"),
         };
 
-        let secs = match parts[1]
-            .strip_prefix("Last modified: ")
-            .and_then(|s| s.parse().ok())
-        {
-            Some(secs) => secs,
-            None => return Err("Invalid timestamp format"),
+        metadata.unix = match (mode, uid, gid, changed) {
+            (Some(mode), Some(uid), Some(gid), Some(changed)) => Some(UnixMetadata {
+                mode,
+                uid,
+                gid,
+                changed,
+            }),
+            (None, None, None, None) => None,
+            _ => return Err("Incomplete mode, ownership or change time"),
         };
+        metadata.link_group = link_group;
 
-        let last_modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
-
-        Ok(FileMetadata {
-            last_modified,
-            size,
-        })
+        Ok(metadata)
     }
+}
+
+//'<seconds>[.<fraction of up to 9 digits>]' to seconds and nanoseconds
+fn parse_timestamp(value: &str) -> Option<(u64, Option<u32>)> {
+    let (secs, fraction) = match value.split_once('.') {
+        Some((secs, fraction)) => (secs, Some(fraction)),
+        None => (value, None),
+    };
+
+    let secs = secs.parse().ok()?;
+
+    let nanos = match fraction {
+        Some(fraction) => {
+            if fraction.is_empty()
+                || fraction.len() > 9
+                || !fraction.bytes().all(|b| b.is_ascii_digit())
+            {
+                return None;
+            }
+            //'.5' is 500000000 nanoseconds
+            Some(format!("{:0<9}", fraction).parse().ok()?)
+        }
+        None => None,
+    };
+
+    Some((secs, nanos))
+}
 
-    pub fn to_string(&self) -> String {
-        format!(
+impl fmt::Display for FileMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
             "Size: {:?}, Last modified: {:?}",
             self.size,
-            self.last_modified
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs()
-        )
+            self.last_modified_secs()
+        )?;
+
+        if let Some(nanos) = self.last_modified_nanos() {
+            write!(f, ".{:09}", nanos)?;
+        }
+
+        if let Some(unix) = &self.unix {
+            let changed = unix.changed.duration_since(UNIX_EPOCH).unwrap_or_default();
+            write!(
+                f,
+                ", Mode: {:o}, Uid: {}, Gid: {}, Changed: {}.{:09}",
+                unix.mode,
+                unix.uid,
+                unix.gid,
+                changed.as_secs(),
+                changed.subsec_nanos()
+            )?;
+        }
+
+        if let Some(link_group) = self.link_group {
+            write!(f, ", Link: {}", link_group)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp() {
+        assert_eq!(parse_timestamp("1733589895"), Some((1733589895, None)));
+        assert_eq!(
+            parse_timestamp("1733589895.123456789"),
+            Some((1733589895, Some(123456789)))
+        );
+        assert_eq!(parse_timestamp("1.5"), Some((1, Some(500_000_000))));
+        assert_eq!(parse_timestamp("1.000000001"), Some((1, Some(1))));
+    }
+
+    #[test]
+    fn invalid_timestamp() {
+        for value in ["", "x", "-1", "1.", "1.1234567890", "1.+5", "1.5x", "1.2.3"] {
+            assert_eq!(parse_timestamp(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut metadata = FileMetadata::new(UNIX_EPOCH + Duration::new(1733589895, 42), 2999880);
+        let line = metadata.to_string();
+        assert_eq!(line, "Size: 2999880, Last modified: 1733589895.000000042");
+
+        let parsed = FileMetadata::new_from_string(&line).unwrap();
+        assert_eq!(parsed.size, 2999880);
+        assert_eq!(parsed.last_modified, metadata.last_modified);
+        assert!(parsed.precise_mtime);
+        assert!(parsed.unix.is_none());
+        assert!(parsed.link_group.is_none());
+
+        metadata.unix = Some(UnixMetadata {
+            mode: 0o100644,
+            uid: 1000,
+            gid: 100,
+            changed: UNIX_EPOCH + Duration::new(1733589896, 500_000_000),
+        });
+        metadata.link_group = Some(3);
+        let line = metadata.to_string();
+        assert_eq!(
+            line,
+            "Size: 2999880, Last modified: 1733589895.000000042, Mode: 100644, Uid: 1000, Gid: 100, Changed: 1733589896.500000000, Link: 3"
+        );
+
+        let parsed = FileMetadata::new_from_string(&line).unwrap();
+        assert_eq!(parsed.unix, metadata.unix);
+        assert_eq!(parsed.link_group, Some(3));
+        assert_eq!(parsed.to_string(), line);
+    }
+
+    #[test]
+    fn whole_seconds() {
+        let parsed = FileMetadata::new_from_string("Size: 10, Last modified: 1733589895").unwrap();
+        assert!(!parsed.precise_mtime);
+        assert_eq!(parsed.last_modified_nanos(), None);
+        assert_eq!(parsed.to_string(), "Size: 10, Last modified: 1733589895");
+
+        //compared in whole seconds against a precise time
+        let precise = FileMetadata::new(UNIX_EPOCH + Duration::new(1733589895, 999), 10);
+        assert!(parsed.same_last_modified(&precise));
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        let parsed =
+            FileMetadata::new_from_string("Size: 10, Last modified: 1.5, Colour: blue").unwrap();
+        assert_eq!(parsed.size, 10);
+        assert_eq!(parsed.last_modified_nanos(), Some(500_000_000));
+    }
+
+    #[test]
+    fn invalid_metadata() {
+        for line in [
+            "",
+            "Size: 10",
+            "Last modified: 1",
+            "Size: x, Last modified: 1",
+            "Size: 10, Last modified: 1.x",
+            "Size: 10 Last modified: 1",
+            "Size: 10, Last modified: 1, Mode: 9",
+            "Size: 10, Last modified: 1, Link: x",
+            //unix fields come together
+            "Size: 10, Last modified: 1, Mode: 644, Uid: 0",
+        ] {
+            assert!(FileMetadata::new_from_string(line).is_err(), "{}", line);
+        }
     }
 }
@@ -6,6 +6,7 @@ use std::path::PathBuf;
 
 /// Represents a file, whether it exists or not (Filesystem, Directory digest)
 /// Has a full path to the file, metadata, and an optional hash
+/// Metadata is only missing for entries of digest files that don't record it (eg. md5sum files)
 ///
 ///
 pub struct FileSt<H>
@@ -15,14 +16,14 @@ where
     pub path: PathBuf,
     pub loaded_hash: Option<H>,
     pub calculated_hash: Option<H>,
-    pub metadata: FileMetadata,
+    pub metadata: Option<FileMetadata>,
 }
 
 impl<H> FileSt<H>
 where
     H: HashValue,
 {
-    pub fn new(path: PathBuf, loaded_hash: Option<H>, metadata: FileMetadata) -> Self {
+    pub fn new(path: PathBuf, loaded_hash: Option<H>, metadata: Option<FileMetadata>) -> Self {
         FileSt {
             path,
             loaded_hash,
@@ -31,6 +32,14 @@ where
         }
     }
 
+    //size from the metadata, or from the filesystem if the metadata is unknown
+    pub fn size(&self) -> u64 {
+        match &self.metadata {
+            Some(metadata) => metadata.size,
+            None => self.path.metadata().map(|m| m.len()).unwrap_or(0),
+        }
+    }
+
     pub fn calc_hash(&mut self) -> io::Result<()> {
         match H::new_hash_file(&self.path) {
             Ok(hash) => {
@@ -61,3 +70,22 @@ impl<H: HashValue> PartialEq for FileSt<H> {
 }
 
 impl<H: HashValue> Eq for FileSt<H> {}
+
+/// A symbolic link recorded in a digest instead of being followed, see SymlinkPolicy
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymlinkSt {
+    pub path: PathBuf,
+    /// The link content as it is, usually relative to the link's directory
+    pub target: PathBuf,
+}
+
+impl SymlinkSt {
+    pub fn new(path: PathBuf, target: PathBuf) -> Self {
+        SymlinkSt { path, target }
+    }
+
+    /// A link whose target doesn't exist (on disk, now)
+    pub fn is_dangling(&self) -> bool {
+        self.path.symlink_metadata().is_ok() && self.path.metadata().is_err()
+    }
+}
@@ -0,0 +1,87 @@
+use crate::file_rep::hash::hex::{bytes_to_hex, hex_to_bytes};
+use crate::file_rep::hash_def::{HashState, HashValue};
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Clone)]
+pub struct HashBLAKE3([u8; 32]);
+
+impl HashState for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    //each chunk is hashed on the current rayon pool
+    fn update_large(&mut self, data: &[u8]) {
+        self.update_rayon(data);
+    }
+}
+
+impl HashValue for HashBLAKE3 {
+    type State = blake3::Hasher;
+
+    fn new_state() -> Self::State {
+        blake3::Hasher::new()
+    }
+
+    fn from_state(state: Self::State) -> Self {
+        Self(state.finalize().into())
+    }
+
+    fn new_from_string<S: AsRef<str>>(input: S) -> Option<Self> {
+        hex_to_bytes(input.as_ref()).map(HashBLAKE3)
+    }
+
+    fn equals(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+
+    fn to_string(&self) -> String {
+        bytes_to_hex(&self.0)
+    }
+
+    fn byte_len() -> usize {
+        32
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        bytes.try_into().ok().map(HashBLAKE3)
+    }
+
+    fn parse_hash_type_string<S: AsRef<str>>(input: S) -> bool {
+        input.as_ref() == "blake3"
+    }
+
+    fn signature_to_string() -> &'static str {
+        "blake3"
+    }
+
+    fn dd_suffix() -> &'static str {
+        "ddblake3"
+    }
+
+    fn sum_suffixes() -> &'static [&'static str] {
+        &["b3"]
+    }
+
+    fn parallel_large_file_hashing() -> bool {
+        true
+    }
+}
+
+impl PartialEq for HashBLAKE3 {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other)
+    }
+}
+
+impl Eq for HashBLAKE3 {}
+
+impl Hash for HashBLAKE3 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(&self.0)
+    }
+}
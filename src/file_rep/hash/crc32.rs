@@ -0,0 +1,89 @@
+use crate::file_rep::hash::hex::{bytes_to_hex, hex_to_bytes};
+use crate::file_rep::hash_def::{HashState, HashValue};
+use crc32fast::Hasher as Crc32Hasher;
+use std::hash::{Hash, Hasher};
+
+/// CRC32 (IEEE), as used by SFV files. Not a cryptographic hash - it only detects accidental corruption.
+#[derive(Debug, Clone)]
+pub struct HashCRC32([u8; 4]);
+
+impl HashState for Crc32Hasher {
+    fn update(&mut self, data: &[u8]) {
+        Crc32Hasher::update(self, data);
+    }
+}
+
+impl HashValue for HashCRC32 {
+    type State = Crc32Hasher;
+
+    fn new_state() -> Self::State {
+        Crc32Hasher::new()
+    }
+
+    fn from_state(state: Self::State) -> Self {
+        Self(state.finalize().to_be_bytes())
+    }
+
+    fn new_from_string<S: AsRef<str>>(input: S) -> Option<Self> {
+        hex_to_bytes(input.as_ref()).map(HashCRC32)
+    }
+
+    fn equals(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+
+    fn to_string(&self) -> String {
+        bytes_to_hex(&self.0)
+    }
+
+    fn byte_len() -> usize {
+        4
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        bytes.try_into().ok().map(HashCRC32)
+    }
+
+    fn parse_hash_type_string<S: AsRef<str>>(input: S) -> bool {
+        input.as_ref() == "crc32"
+    }
+
+    fn signature_to_string() -> &'static str {
+        "crc32"
+    }
+
+    fn dd_suffix() -> &'static str {
+        "ddcrc32"
+    }
+
+    //coreutils has no CRC32 tool ('cksum' is a different CRC), SFV files are used instead
+    fn sum_suffixes() -> &'static [&'static str] {
+        &[]
+    }
+
+    fn sfv_compatible() -> bool {
+        true
+    }
+
+    fn is_cryptographic() -> bool {
+        false
+    }
+}
+
+impl PartialEq for HashCRC32 {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other)
+    }
+}
+
+impl Eq for HashCRC32 {}
+
+impl Hash for HashCRC32 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(&self.0)
+    }
+}
@@ -0,0 +1,35 @@
+/// Parses a hex string of exactly `N * 2` characters into `N` bytes
+pub fn hex_to_bytes<const N: usize>(input: &str) -> Option<[u8; N]> {
+    if input.len() != N * 2 {
+        return None;
+    }
+
+    let mut bytes = [0u8; N];
+
+    //pairs of hex chars to bytes
+    for (i, chunk) in input.as_bytes().chunks(2).enumerate() {
+        //parse high and low nibble
+        let high = hex_char_to_int(chunk[0])?;
+        let low = hex_char_to_int(chunk[1])?;
+
+        bytes[i] = (high << 4) | low;
+    }
+
+    Some(bytes)
+}
+
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>()
+}
+
+fn hex_char_to_int(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
@@ -1,49 +1,24 @@
-use crate::file_rep::file_hasher::hash_file;
-use crate::file_rep::hash_def::{HashValue};
+use crate::file_rep::file_hasher::DigestState;
+use crate::file_rep::hash::hex::{bytes_to_hex, hex_to_bytes};
+use crate::file_rep::hash_def::HashValue;
 use std::hash::{Hash, Hasher};
-use std::io;
-use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub struct HashMD5([u8; 16]);
 
 impl HashValue for HashMD5 {
-    fn new_hash_file(path: &PathBuf) -> io::Result<Self> {
-        let result = hash_file::<md5::Md5>(path)?;
-        Ok(Self(result.into())) //directly convert since compile time known size
-    }
-
-    fn new_from_string<S: AsRef<str>>(input: S) -> Option<Self> {
-        let input = input.as_ref();
-
-        if input.len() != 32 {
-            return None;
-        }
-
-        let mut bytes = [0u8; 16];
-
-        //pairs of hex chars to bytes
-        for (i, chunk) in input.as_bytes().chunks(2).enumerate() {
-            //two chars per byte
-            if chunk.len() != 2 {
-                return None;
-            }
+    type State = DigestState<md5::Md5>;
 
-            //parse high and low nibble
-            let high = match hex_char_to_int(chunk[0]) {
-                Some(v) => v,
-                None => return None,
-            };
-
-            let low = match hex_char_to_int(chunk[1]) {
-                Some(v) => v,
-                None => return None,
-            };
+    fn new_state() -> Self::State {
+        DigestState::default()
+    }
 
-            bytes[i] = (high << 4) | low;
-        }
+    fn from_state(state: Self::State) -> Self {
+        Self(state.finalize().into()) //directly convert since compile time known size
+    }
 
-        Some(HashMD5(bytes))
+    fn new_from_string<S: AsRef<str>>(input: S) -> Option<Self> {
+        hex_to_bytes(input.as_ref()).map(HashMD5)
     }
 
     fn equals(&self, other: &Self) -> bool {
@@ -51,10 +26,19 @@ impl HashValue for HashMD5 {
     }
 
     fn to_string(&self) -> String {
-        self.0
-            .iter()
-            .map(|byte| format!("{:02x}", byte))
-            .collect::<String>()
+        bytes_to_hex(&self.0)
+    }
+
+    fn byte_len() -> usize {
+        16
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        bytes.try_into().ok().map(HashMD5)
     }
 
     fn parse_hash_type_string<S: AsRef<str>>(input: S) -> bool {
@@ -64,6 +48,14 @@ impl HashValue for HashMD5 {
     fn signature_to_string() -> &'static str {
         "md5"
     }
+
+    fn dd_suffix() -> &'static str {
+        "ddmd5"
+    }
+
+    fn sum_suffixes() -> &'static [&'static str] {
+        &["md5"]
+    }
 }
 
 impl PartialEq for HashMD5 {
@@ -79,12 +71,3 @@ impl Hash for HashMD5 {
         state.write(&self.0)
     }
 }
-
-fn hex_char_to_int(c: u8) -> Option<u8> {
-    match c {
-        b'0'..=b'9' => Some(c - b'0'),
-        b'a'..=b'f' => Some(c - b'a' + 10),
-        b'A'..=b'F' => Some(c - b'A' + 10),
-        _ => None,
-    }
-}
@@ -1 +1,7 @@
-pub mod md5;
\ No newline at end of file
+pub mod blake3;
+pub mod crc32;
+pub(crate) mod hex;
+pub mod md5;
+pub mod pair;
+pub mod sha256;
+pub mod xxh3;
\ No newline at end of file
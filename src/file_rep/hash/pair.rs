@@ -0,0 +1,126 @@
+use crate::file_rep::hash_def::HashValue;
+use std::hash::{Hash, Hasher};
+
+/// Two hashes of the same file, both calculated in a single read pass. Nested for more than two,
+/// eg. HashPair<HashMD5, HashPair<HashSHA256, HashBLAKE3>>.
+/// String form is '<a>+<b>', the name is '<name of a>+<name of b>' (eg. 'md5+sha256').
+/// Every pair type needs its PairNames, nested ones included.
+#[derive(Debug, Clone)]
+pub struct HashPair<A: HashValue, B: HashValue> {
+    a: A,
+    b: B,
+}
+
+/// Names of a pair type, spelled out at compile time by the macro that registers the pairs.
+/// NAME is '<name of a>+<name of b>', DD_SUFFIX is 'dd<a><b>' (eg. 'ddmd5sha256'), SUM_SUFFIXES
+/// are the ones of a followed by the ones of b.
+pub trait PairNames {
+    const NAME: &'static str;
+    const DD_SUFFIX: &'static str;
+    const SUM_SUFFIXES: &'static [&'static str];
+}
+
+impl<A, B> HashValue for HashPair<A, B>
+where
+    A: HashValue,
+    B: HashValue,
+    Self: PairNames,
+{
+    type State = (A::State, B::State);
+
+    fn new_state() -> Self::State {
+        (A::new_state(), B::new_state())
+    }
+
+    fn from_state(state: Self::State) -> Self {
+        HashPair {
+            a: A::from_state(state.0),
+            b: B::from_state(state.1),
+        }
+    }
+
+    fn new_from_string<S: AsRef<str>>(input: S) -> Option<Self> {
+        //A nested pair on the right side gets the rest, eg. 'b+c'
+        let (a, b) = input.as_ref().split_once('+')?;
+
+        Some(HashPair {
+            a: A::new_from_string(a)?,
+            b: B::new_from_string(b)?,
+        })
+    }
+
+    fn equals(&self, other: &Self) -> bool {
+        self.a.equals(&other.a) && self.b.equals(&other.b)
+    }
+
+    fn to_string(&self) -> String {
+        format!("{}+{}", self.a.to_string(), self.b.to_string())
+    }
+
+    fn byte_len() -> usize {
+        A::byte_len() + B::byte_len()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        [self.a.to_bytes(), self.b.to_bytes()].concat()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::byte_len() {
+            return None;
+        }
+        let (a, b) = bytes.split_at(A::byte_len());
+
+        Some(HashPair {
+            a: A::from_bytes(a)?,
+            b: B::from_bytes(b)?,
+        })
+    }
+
+    fn parse_hash_type_string<S: AsRef<str>>(input: S) -> bool {
+        input.as_ref() == Self::signature_to_string()
+    }
+
+    fn signature_to_string() -> &'static str {
+        Self::NAME
+    }
+
+    fn dd_suffix() -> &'static str {
+        Self::DD_SUFFIX
+    }
+
+    fn sum_suffixes() -> &'static [&'static str] {
+        Self::SUM_SUFFIXES
+    }
+
+    fn sum_string(&self, sum_suffix: &str) -> Option<String> {
+        self.a
+            .sum_string(sum_suffix)
+            .or_else(|| self.b.sum_string(sum_suffix))
+    }
+
+    //both hashes are checked, so one of them being cryptographic is enough to detect tampering
+    fn is_cryptographic() -> bool {
+        A::is_cryptographic() || B::is_cryptographic()
+    }
+
+    //the tuple state feeds update_large to both, so one parallel side is worth a whole pool
+    fn parallel_large_file_hashing() -> bool {
+        A::parallel_large_file_hashing() || B::parallel_large_file_hashing()
+    }
+}
+
+impl<A: HashValue, B: HashValue> PartialEq for HashPair<A, B> {
+    fn eq(&self, other: &Self) -> bool {
+        self.a.equals(&other.a) && self.b.equals(&other.b)
+    }
+}
+
+impl<A: HashValue, B: HashValue> Eq for HashPair<A, B> {}
+
+impl<A: HashValue, B: HashValue> Hash for HashPair<A, B> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.a.hash(state);
+        self.b.hash(state);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::file_rep::file_hasher::DigestState;
+use crate::file_rep::hash::hex::{bytes_to_hex, hex_to_bytes};
+use crate::file_rep::hash_def::HashValue;
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Clone)]
+pub struct HashSHA256([u8; 32]);
+
+impl HashValue for HashSHA256 {
+    type State = DigestState<sha2::Sha256>;
+
+    fn new_state() -> Self::State {
+        DigestState::default()
+    }
+
+    fn from_state(state: Self::State) -> Self {
+        Self(state.finalize().into()) //directly convert since compile time known size
+    }
+
+    fn new_from_string<S: AsRef<str>>(input: S) -> Option<Self> {
+        hex_to_bytes(input.as_ref()).map(HashSHA256)
+    }
+
+    fn equals(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+
+    fn to_string(&self) -> String {
+        bytes_to_hex(&self.0)
+    }
+
+    fn byte_len() -> usize {
+        32
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        bytes.try_into().ok().map(HashSHA256)
+    }
+
+    fn parse_hash_type_string<S: AsRef<str>>(input: S) -> bool {
+        input.as_ref() == "sha256"
+    }
+
+    fn signature_to_string() -> &'static str {
+        "sha256"
+    }
+
+    fn dd_suffix() -> &'static str {
+        "ddsha256"
+    }
+
+    fn sum_suffixes() -> &'static [&'static str] {
+        &["sha256"]
+    }
+}
+
+impl PartialEq for HashSHA256 {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other)
+    }
+}
+
+impl Eq for HashSHA256 {}
+
+impl Hash for HashSHA256 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(&self.0)
+    }
+}
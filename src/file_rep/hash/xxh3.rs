@@ -0,0 +1,85 @@
+use crate::file_rep::hash::hex::{bytes_to_hex, hex_to_bytes};
+use crate::file_rep::hash_def::{HashState, HashValue};
+use std::hash::{Hash, Hasher};
+use xxhash_rust::xxh3::Xxh3;
+
+/// 128 bit XXH3. Not a cryptographic hash - only meant for quick bit-rot scrubs.
+#[derive(Debug, Clone)]
+pub struct HashXXH3([u8; 16]);
+
+impl HashState for Xxh3 {
+    fn update(&mut self, data: &[u8]) {
+        Xxh3::update(self, data);
+    }
+}
+
+impl HashValue for HashXXH3 {
+    type State = Xxh3;
+
+    fn new_state() -> Self::State {
+        Xxh3::new()
+    }
+
+    fn from_state(state: Self::State) -> Self {
+        //canonical (big endian) form, same as xxhsum
+        Self(state.digest128().to_be_bytes())
+    }
+
+    fn new_from_string<S: AsRef<str>>(input: S) -> Option<Self> {
+        hex_to_bytes(input.as_ref()).map(HashXXH3)
+    }
+
+    fn equals(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+
+    fn to_string(&self) -> String {
+        bytes_to_hex(&self.0)
+    }
+
+    fn byte_len() -> usize {
+        16
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        bytes.try_into().ok().map(HashXXH3)
+    }
+
+    fn parse_hash_type_string<S: AsRef<str>>(input: S) -> bool {
+        input.as_ref() == "xxh3_128"
+    }
+
+    fn signature_to_string() -> &'static str {
+        "xxh3_128"
+    }
+
+    fn dd_suffix() -> &'static str {
+        "ddxxh3"
+    }
+
+    fn sum_suffixes() -> &'static [&'static str] {
+        &["xxh128"]
+    }
+
+    fn is_cryptographic() -> bool {
+        false
+    }
+}
+
+impl PartialEq for HashXXH3 {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other)
+    }
+}
+
+impl Eq for HashXXH3 {}
+
+impl Hash for HashXXH3 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(&self.0)
+    }
+}
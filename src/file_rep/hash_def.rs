@@ -1,34 +1,74 @@
-use std::hash::{Hash};
+use crate::constants::{FILE_BUFFER_SIZE, LARGE_FILE_BUFFER_SIZE};
+use crate::file_rep::file_hasher::read_file_chunks;
+use std::any::Any;
+use std::hash::Hash;
 use std::io;
-use std::path::PathBuf;
+use std::path::Path;
 //alternative: simply hash with Vec<u8> but then its slower
 
-pub enum HashType {
-    MD5,
-}
+/// Incremental hashing state, fed with the file contents chunk by chunk
+pub trait HashState: Send {
+    fn update(&mut self, data: &[u8]);
 
-pub fn hash_type_suffix_parse<S: AsRef<str>>(input: S) -> Option<HashType> {
-    match input.as_ref() {
-        "ddmd5" => Some(HashType::MD5),
-        _ => None,
+    /// Same as update, for the big chunks of large files. States that can spread a chunk over
+    /// multiple threads do so, see HashValue::parallel_large_file_hashing
+    fn update_large(&mut self, data: &[u8]) {
+        self.update(data);
     }
 }
 
-pub fn hash_type_to_suffix(hash_type: &HashType) -> &'static str {
-    match hash_type {
-        HashType::MD5 => "ddmd5",
+//both states are fed the same data, so a single read pass produces two hashes
+impl<A: HashState, B: HashState> HashState for (A, B) {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+        self.1.update(data);
+    }
+
+    fn update_large(&mut self, data: &[u8]) {
+        self.0.update_large(data);
+        self.1.update_large(data);
     }
 }
 
-pub fn hash_string_to_type<S: AsRef<str>>(input: S) -> Option<HashType> {
-    match input.as_ref() {
-        "md5" => Some(HashType::MD5),
-        _ => None,
+/// Type erased HashState, so a file can be hashed for an engine of a different hash type
+pub trait AnyHashState: HashState {
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: HashState + 'static> AnyHashState for T {
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
     }
 }
 
 pub trait HashValue: Sized + Eq + Hash + Clone {
-    fn new_hash_file(path: &PathBuf) -> io::Result<Self>;
+    type State: HashState + 'static;
+
+    fn new_state() -> Self::State;
+    fn from_state(state: Self::State) -> Self;
+
+    fn new_hash_file(path: &Path) -> io::Result<Self> {
+        let mut state = Self::new_state();
+        let mut buffer = vec![0; FILE_BUFFER_SIZE];
+        read_file_chunks(path, &mut buffer, |chunk| state.update(chunk))?;
+
+        Ok(Self::from_state(state))
+    }
+
+    //a file the planner found larger than SMALL_FILE_SIZE_THRESHOLD
+    fn new_hash_large_file(path: &Path) -> io::Result<Self> {
+        if !Self::parallel_large_file_hashing() {
+            return Self::new_hash_file(path);
+        }
+
+        //Read in big chunks, each is spread over the threads of the current rayon pool
+        let mut state = Self::new_state();
+        let mut buffer = vec![0; LARGE_FILE_BUFFER_SIZE];
+        read_file_chunks(path, &mut buffer, |chunk| state.update_large(chunk))?;
+
+        Ok(Self::from_state(state))
+    }
+
     fn new_from_string<S: AsRef<str>>(input: S) -> Option<Self>;
 
     //equality
@@ -37,7 +77,40 @@ pub trait HashValue: Sized + Eq + Hash + Clone {
     //to string
     fn to_string(&self) -> String;
 
+    //fixed width raw form, used by the binary digest format
+    fn byte_len() -> usize;
+    fn to_bytes(&self) -> Vec<u8>;
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+
     fn parse_hash_type_string<S: AsRef<str>>(input: S) -> bool;
 
     fn signature_to_string() -> &'static str;
+
+    //file extension of directory digests using this hash
+    fn dd_suffix() -> &'static str;
+
+    //file extensions of md5sum style checksum files (eg. 'md5' for 'x.md5' or 'MD5SUMS'), one per
+    //contained checksum - empty if coreutils has no tool for it
+    fn sum_suffixes() -> &'static [&'static str];
+
+    //the checksum written to a md5sum style file with the given suffix
+    fn sum_string(&self, sum_suffix: &str) -> Option<String> {
+        (Self::sum_suffixes() == [sum_suffix]).then(|| self.to_string())
+    }
+
+    //true if SFV files (which always hold CRC32 checksums) can be read into and written from it
+    fn sfv_compatible() -> bool {
+        false
+    }
+
+    //false for checksums that only detect accidental corruption, not deliberate tampering
+    fn is_cryptographic() -> bool {
+        true
+    }
+
+    //true if the state's update_large spreads a chunk over multiple threads, so large files are
+    //hashed one at a time on the whole pool
+    fn parallel_large_file_hashing() -> bool {
+        false
+    }
 }
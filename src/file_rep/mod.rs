@@ -1,6 +1,7 @@
-mod file_hasher;
+pub mod file_hasher;
 pub mod file_metadata;
 pub mod file_st;
 pub mod hash_def;
 pub mod hash;
 pub mod directory_walker;
+pub mod path_filter;
@@ -0,0 +1,138 @@
+use crate::constants::IGNORE_FILE_NAME;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Decides which files and directories are left out of a digest, by gitignore-style patterns:
+/// the patterns of the run, and the .sfisumignore files in the tree.
+///
+/// As in gitignore, a pattern starting with '!' includes what an earlier one excluded, and a
+/// .sfisumignore deeper in the tree wins over the ones above it. The patterns of the run win over
+/// all of them.
+pub struct PathFilter {
+    base_path: PathBuf,
+    matcher: Gitignore,
+    //matchers of the .sfisumignore files by directory, None if the directory has none. Shared by
+    //the threads of the walker.
+    ignore_files: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl PathFilter {
+    pub fn new(base_path: PathBuf, patterns: Vec<String>) -> Result<Self, String> {
+        let mut builder = GitignoreBuilder::new(&base_path);
+        for pattern in patterns.iter() {
+            builder
+                .add_line(None, pattern)
+                .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+        }
+        let matcher = builder
+            .build()
+            .map_err(|e| format!("Invalid patterns: {}", e))?;
+
+        Ok(PathFilter {
+            base_path,
+            matcher,
+            ignore_files: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Whether a file or directory is left out. The directories it is in are not checked, the
+    /// walker doesn't enter excluded directories in the first place.
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        match path.parent() {
+            Some(dir) => self.for_dir(dir).is_excluded(path, is_dir),
+            None => DirFilter {
+                filter: self,
+                ignore_files: Vec::new(),
+            }
+            .is_excluded(path, is_dir),
+        }
+    }
+
+    /// The filter of the entries of one directory, with the .sfisumignore files that apply there
+    /// looked up once, instead of for every entry
+    pub fn for_dir(&self, dir: &Path) -> DirFilter<'_> {
+        let ignore_files = dir
+            .ancestors()
+            .take_while(|dir| dir.starts_with(&self.base_path))
+            .filter_map(|dir| self.ignore_file_matcher(dir))
+            .collect();
+
+        DirFilter {
+            filter: self,
+            ignore_files,
+        }
+    }
+
+    /// Like is_excluded, for a path that may be in an excluded directory, eg. a digest entry
+    pub fn is_excluded_with_parents(&self, path: &Path, is_dir: bool) -> bool {
+        let mut dirs: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.base_path) && *dir != self.base_path)
+            .collect();
+        dirs.reverse();
+
+        for dir in dirs {
+            if self.is_excluded(dir, true) {
+                return true;
+            }
+        }
+
+        self.is_excluded(path, is_dir)
+    }
+
+    fn ignore_file_matcher(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        let ignore_files = self
+            .ignore_files
+            .lock()
+            .expect("BUG: Ignore file cache is poisoned");
+        if let Some(matcher) = ignore_files.get(dir) {
+            return matcher.clone();
+        }
+        //The file is read without holding the lock, two threads reading it at once is harmless
+        drop(ignore_files);
+
+        let ignore_file = dir.join(IGNORE_FILE_NAME);
+        //Lines that are not valid patterns are skipped, like git does
+        let matcher = ignore_file
+            .is_file()
+            .then(|| Arc::new(Gitignore::new(ignore_file).0));
+
+        self.ignore_files
+            .lock()
+            .expect("BUG: Ignore file cache is poisoned")
+            .insert(dir.to_path_buf(), matcher.clone());
+        matcher
+    }
+}
+
+/// A PathFilter for the entries of a single directory, see PathFilter::for_dir
+pub struct DirFilter<'a> {
+    filter: &'a PathFilter,
+    //matchers of the .sfisumignore files of the directory and the ones above it, nearest first
+    ignore_files: Vec<Arc<Gitignore>>,
+}
+
+impl DirFilter<'_> {
+    /// Same as PathFilter::is_excluded, for an entry of the directory
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        match self.filter.matcher.matched(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+
+        for matcher in self.ignore_files.iter() {
+            match matcher.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+
+        false
+    }
+}
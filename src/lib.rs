@@ -0,0 +1,11 @@
+//! sfisum as a library, for tools that embed it with hash algorithms of their own: implement
+//! HashValue, add it with register_hash_algorithm, then launch the Sfisum console.
+mod constants;
+mod engine;
+mod file_rep;
+mod sfisum_instance;
+mod util;
+
+pub use engine::engine_factory::{register_hash_algorithm, HashAlgorithm};
+pub use file_rep::hash_def::{HashState, HashValue};
+pub use sfisum_instance::Sfisum;
@@ -1,10 +1,4 @@
-use crate::sfisum_instance::Sfisum;
-
-mod constants;
-mod engine;
-mod file_rep;
-mod sfisum_instance;
-mod util;
+use sfisum::Sfisum;
 
 fn main() {
     let instance: Sfisum = Sfisum::new();
@@ -1,10 +1,19 @@
-use crate::engine::dd_file_rw::parse_dd_hash_type;
+use crate::constants::DEFAULT_HASH_TYPE;
+use crate::engine::dd_compression::Compression;
+use crate::engine::dd_file_rw::{parse_dd_hash_type, DigestFormat};
+use crate::engine::dd_signature::{
+    generate_key_pair, load_signing_key, load_verifying_key, SignaturePolicy,
+};
 use crate::engine::engine::EngineAny;
-use crate::engine::engine_factory::{create_engine, dd_filename_to_hash_type};
-use crate::file_rep::hash_def::HashType;
+use crate::engine::engine_factory::{
+    dd_filename_to_hash_type, parse_hash_algorithm, single_hash_algorithm_names, HashAlgorithm,
+};
+use crate::file_rep::directory_walker::SymlinkPolicy;
 use crate::util::console_text_formatter::{colorize_txt, TextColor};
-use std::path::PathBuf;
+use ed25519_dalek::SigningKey;
+use std::path::{Path, PathBuf};
 
+#[derive(Default)]
 pub struct Sfisum {}
 
 impl Sfisum {
@@ -21,7 +30,10 @@ impl Sfisum {
             3) Fast Refresh Directory Digest\n\
             4) Full Refresh Directory Digest\n\
             5) Find duplicates\n\
-            6) Exit\n"
+            6) Migrate Directory Digest to another hash type\n\
+            7) Export Directory Digest to another format\n\
+            8) Generate Ed25519 signing key pair\n\
+            9) Exit\n"
             );
 
             let mut input = String::new();
@@ -44,7 +56,7 @@ impl Sfisum {
                 }
             };
 
-            if input == 6 {
+            if input == 9 {
                 break;
             }
 
@@ -54,6 +66,9 @@ impl Sfisum {
                 3 => self.refresh_cui(true),
                 4 => self.refresh_cui(false),
                 5 => self.find_duplicates_cui(),
+                6 => self.migrate_cui(),
+                7 => self.export_cui(),
+                8 => self.generate_key_pair_cui(),
                 _ => println!(
                     "{}",
                     colorize_txt(
@@ -65,6 +80,14 @@ impl Sfisum {
         }
     }
 
+    //'Digest made on host X from /mnt/a by fast refresh ...', if the digest records it
+    fn print_digest_header(&self, engine: &dyn EngineAny) {
+        let header = engine.loaded_header();
+        if !header.is_empty() {
+            println!("Digest {}\n", header);
+        }
+    }
+
     fn make_engine_from_dd_file_path(&self, dd_file_path: &str) -> Option<Box<dyn EngineAny>> {
         let inferred_hash_type = match dd_filename_to_hash_type(dd_file_path) {
             Some(hash_type) => hash_type,
@@ -80,7 +103,7 @@ impl Sfisum {
             },
         };
 
-        let engine: Box<dyn EngineAny> = create_engine(inferred_hash_type);
+        let engine: Box<dyn EngineAny> = inferred_hash_type.create_engine();
         Some(engine)
     }
 
@@ -99,12 +122,51 @@ impl Sfisum {
         }
         let path = input.trim();
 
+        let hash_type = match self.hash_type_dialog(
+            "Enter the hash type to generate the digest with",
+            Some(DEFAULT_HASH_TYPE),
+        ) {
+            Some(hash_type) => hash_type,
+            None => return,
+        };
+
+        let symlink_policy = match self.symlink_policy_dialog() {
+            Some(symlink_policy) => symlink_policy,
+            None => return,
+        };
+
+        let ignore_patterns = self.ignore_patterns_dialog(
+            "Enter patterns of files to leave out, or press enter for none",
+        );
+
+        //Only unix has a mode and owners to record
+        let record_unix_metadata = cfg!(unix)
+            && self.yes_no_dialog("Record the permissions, owner and change time of files?");
+
+        //Mount points are told apart by the device id, which only unix exposes
+        let one_file_system = cfg!(unix)
+            && self.yes_no_dialog(
+                "Stay on the filesystem of the directory, leaving out the ones mounted below it?",
+            );
+
+        let record_dirs = self.yes_no_dialog("Record the directories, so empty ones are kept?");
+
+        //Hardlinks are told apart by the inode, which only unix exposes
+        let record_link_groups =
+            cfg!(unix) && self.yes_no_dialog("Record which files are hardlinks of each other?");
+
         println!("Path loaded. Press enter to continue.");
         let mut input = String::new();
         std::io::stdin().read_line(&mut input).unwrap();
 
-        let mut engine: Box<dyn EngineAny> = create_engine(HashType::MD5);
+        let mut engine: Box<dyn EngineAny> = hash_type.create_engine();
         engine.set_paths(PathBuf::new(), path.into());
+        engine.set_record_unix_metadata(record_unix_metadata);
+        engine.set_symlink_policy(symlink_policy);
+        engine.set_ignore_patterns(ignore_patterns);
+        engine.set_one_file_system(one_file_system);
+        engine.set_record_dirs(record_dirs);
+        engine.set_record_link_groups(record_link_groups);
 
         match engine.start_generate() {
             Ok(_) => println!(
@@ -146,7 +208,7 @@ impl Sfisum {
 
         println!("{}", colorize_txt(TextColor::BrightBlue, "\n######\n"));
 
-        self.save_digest_file_dialog(&mut engine);
+        self.save_digest_file_dialog(&mut engine, DigestFormat::Native, Compression::None);
     }
 
     pub fn validate_cui(&self) {
@@ -179,6 +241,11 @@ impl Sfisum {
 
         let digest_path = input.trim();
 
+        let signature_policy = match self.signature_policy_dialog() {
+            Some(policy) => policy,
+            None => return,
+        };
+
         println!("Paths loaded. Press enter to continue.");
         let mut input = String::new();
         std::io::stdin().read_line(&mut input).unwrap();
@@ -189,9 +256,13 @@ impl Sfisum {
         };
 
         engine.set_paths(PathBuf::from(digest_path), PathBuf::from(base_dir_path));
+        engine.set_signature_policy(signature_policy);
 
         match engine.start_validate() {
-            Ok(_) => println!("{}", colorize_txt(TextColor::Green, "Digest validated.\n")),
+            Ok(_) => {
+                println!("{}", colorize_txt(TextColor::Green, "Digest validated.\n"));
+                self.print_digest_header(engine.as_ref());
+            }
             Err(e) => {
                 println!(
                     "{}",
@@ -258,6 +329,10 @@ impl Sfisum {
 
         let digest_path = input.trim();
 
+        let ignore_patterns = self.ignore_patterns_dialog(
+            "Enter patterns of files to leave out besides the ones of the digest, or press enter for none",
+        );
+
         println!("Paths loaded. Press enter to continue.");
         let mut input = String::new();
         std::io::stdin().read_line(&mut input).unwrap();
@@ -268,6 +343,7 @@ impl Sfisum {
         };
 
         engine.set_paths(PathBuf::from(digest_path), PathBuf::from(base_dir_path));
+        engine.set_ignore_patterns(ignore_patterns);
 
         let operation_result = if fast {
             engine.start_fast_refresh()
@@ -282,7 +358,10 @@ impl Sfisum {
         };
 
         match operation_result {
-            Ok(_) => println!("{}", colorize_txt(TextColor::Green, result_text)),
+            Ok(_) => {
+                println!("{}", colorize_txt(TextColor::Green, result_text));
+                self.print_digest_header(engine.as_ref());
+            }
             Err(e) => {
                 println!(
                     "{}",
@@ -323,10 +402,379 @@ impl Sfisum {
 
         println!("{}", colorize_txt(TextColor::BrightBlue, "\n######\n"));
 
-        self.save_digest_file_dialog(&mut engine);
+        self.save_digest_file_dialog(&mut engine, DigestFormat::Native, Compression::None);
+    }
+
+    fn migrate_cui(&self) {
+        println!("Enter the path to the base directory:");
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).is_err() {
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::Red,
+                    "Invalid input. Please enter a valid path.\n"
+                )
+            );
+            return;
+        }
+        let base_dir_path = input.trim().to_string();
+
+        println!("Enter the path to the existing digest file:");
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).is_err() {
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::Red,
+                    "Invalid input. Please enter a valid path.\n"
+                )
+            );
+            return;
+        }
+        let digest_path = input.trim().to_string();
+
+        let target_hash_type =
+            match self.hash_type_dialog("Enter the hash type to migrate to", None) {
+                Some(hash_type) => hash_type,
+                None => return,
+            };
+
+        println!("Paths loaded. Press enter to continue.");
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).unwrap();
+
+        let mut engine: Box<dyn EngineAny> = match self.make_engine_from_dd_file_path(&digest_path)
+        {
+            Some(engine) => engine,
+            None => return,
+        };
+
+        engine.set_paths(PathBuf::from(digest_path), PathBuf::from(base_dir_path));
+
+        match engine.start_migrate(target_hash_type) {
+            Ok(_) => {
+                println!("{}", colorize_txt(TextColor::Green, "Digest migrated.\n"));
+                self.print_digest_header(engine.as_ref());
+            }
+            Err(e) => {
+                println!(
+                    "{}",
+                    colorize_txt(TextColor::Red, &format!("Error during migration: {}\n", e))
+                );
+                return;
+            }
+        }
+
+        let event_count = engine.event_count_migrate();
+        if event_count > 0 {
+            'discard_block: {
+                println!(
+                    "{}",
+                    colorize_txt(
+                        TextColor::BrightBlue,
+                        &format!("There are {} events that occurred during migration. Press enter to view them, or 'd' to discard.\n", event_count)
+                    )
+                );
+
+                let mut input = String::new();
+                std::io::stdin().read_line(&mut input).unwrap();
+
+                if input.trim() == "d" {
+                    break 'discard_block;
+                }
+
+                engine.print_log();
+            }
+        } else {
+            println!("No events occurred during migration.\n");
+        }
+
+        println!("{}", colorize_txt(TextColor::BrightBlue, "\n######\n"));
+
+        self.save_digest_file_dialog(&mut engine, DigestFormat::Native, Compression::None);
+    }
+
+    fn export_cui(&self) {
+        println!("Enter the path to the existing digest file:");
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).is_err() {
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::Red,
+                    "Invalid input. Please enter a valid path.\n"
+                )
+            );
+            return;
+        }
+        let digest_path = input.trim().to_string();
+
+        println!("Enter the format to export to (native, json, binary, coreutils, sfv):");
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).is_err() {
+            println!(
+                "{}",
+                colorize_txt(TextColor::Red, "Invalid input. Please enter a format.\n")
+            );
+            return;
+        }
+        let format = match input.trim() {
+            "native" => DigestFormat::Native,
+            "json" => DigestFormat::Json,
+            "binary" => DigestFormat::Binary,
+            "coreutils" => DigestFormat::Coreutils,
+            "sfv" => DigestFormat::Sfv,
+            other => {
+                println!(
+                    "{}",
+                    colorize_txt(
+                        TextColor::Red,
+                        &format!(
+                            "Unsupported format '{}'. Supported formats: native, json, binary, coreutils, sfv\n",
+                            other
+                        )
+                    )
+                );
+                return;
+            }
+        };
+
+        println!("Enter the compression (none, gz, zst), or press enter for none:");
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).is_err() {
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::Red,
+                    "Invalid input. Please enter a compression.\n"
+                )
+            );
+            return;
+        }
+        let compression = match input.trim() {
+            "" | "none" => Compression::None,
+            "gz" => Compression::Gzip,
+            "zst" => Compression::Zstd,
+            other => {
+                println!(
+                    "{}",
+                    colorize_txt(
+                        TextColor::Red,
+                        &format!(
+                            "Unsupported compression '{}'. Supported compressions: none, gz, zst\n",
+                            other
+                        )
+                    )
+                );
+                return;
+            }
+        };
+
+        let mut engine: Box<dyn EngineAny> = match self.make_engine_from_dd_file_path(&digest_path)
+        {
+            Some(engine) => engine,
+            None => return,
+        };
+
+        engine.set_paths(PathBuf::from(digest_path), PathBuf::new());
+
+        match engine.start_export() {
+            Ok(_) => {
+                println!("{}", colorize_txt(TextColor::Green, "Digest loaded.\n"));
+                self.print_digest_header(engine.as_ref());
+            }
+            Err(e) => {
+                println!(
+                    "{}",
+                    colorize_txt(TextColor::Red, &format!("Error during export: {}\n", e))
+                );
+                return;
+            }
+        }
+
+        self.save_digest_file_dialog(&mut engine, format, compression);
+    }
+
+    fn hash_type_dialog(&self, prompt: &str, default: Option<&str>) -> Option<HashAlgorithm> {
+        let names = format!(
+            "{}, or two joined by '+' to compute both in one pass, eg. md5+sha256",
+            single_hash_algorithm_names().join(", ")
+        );
+        match default {
+            Some(default) => println!("{} ({}), or press enter for {}:", prompt, names, default),
+            None => println!("{} ({}):", prompt, names),
+        }
+
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).is_err() {
+            println!(
+                "{}",
+                colorize_txt(TextColor::Red, "Invalid input. Please enter a hash type.\n")
+            );
+            return None;
+        }
+
+        let name = match (input.trim(), default) {
+            ("", Some(default)) => default,
+            (name, _) => name,
+        };
+
+        match parse_hash_algorithm(name) {
+            Ok(hash_type) => Some(hash_type),
+            Err(e) => {
+                println!("{}", colorize_txt(TextColor::Red, &format!("{}\n", e)));
+                None
+            }
+        }
+    }
+
+    fn symlink_policy_dialog(&self) -> Option<SymlinkPolicy> {
+        println!(
+            "Enter what to do with symbolic links (skip, follow, record), or press enter for {}:",
+            SymlinkPolicy::default().as_str()
+        );
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).is_err() {
+            return None;
+        }
+
+        let name = input.trim();
+        if name.is_empty() {
+            return Some(SymlinkPolicy::default());
+        }
+
+        match SymlinkPolicy::parse(name) {
+            Some(symlink_policy) => Some(symlink_policy),
+            None => {
+                println!(
+                    "{}",
+                    colorize_txt(
+                        TextColor::Red,
+                        &format!("Unknown symbolic link policy '{}'\n", name)
+                    )
+                );
+                None
+            }
+        }
+    }
+
+    //Patterns are separated by spaces, .sfisumignore files in the tree are always applied
+    fn ignore_patterns_dialog(&self, prompt: &str) -> Vec<String> {
+        println!(
+            "{} (gitignore syntax separated by spaces, eg. '.git/ *.tmp !keep.tmp'):",
+            prompt
+        );
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).is_err() {
+            return Vec::new();
+        }
+
+        input.split_whitespace().map(|p| p.to_string()).collect()
     }
 
-    fn save_digest_file_dialog(&self, engine: &mut Box<dyn EngineAny>) {
+    fn yes_no_dialog(&self, prompt: &str) -> bool {
+        println!("{} (y/n)", prompt);
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).is_err() {
+            return false;
+        }
+
+        input.trim() == "y"
+    }
+
+    fn signature_policy_dialog(&self) -> Option<SignaturePolicy> {
+        println!("Enter the path to a trusted Ed25519 public key to verify the digest signature, or press enter to skip:");
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).is_err() {
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::Red,
+                    "Invalid input. Please enter a valid path.\n"
+                )
+            );
+            return None;
+        }
+
+        let key_path = input.trim();
+        if key_path.is_empty() {
+            return Some(SignaturePolicy::Ignore);
+        }
+
+        let key = match load_verifying_key(Path::new(key_path)) {
+            Ok(key) => key,
+            Err(e) => {
+                println!("{}", colorize_txt(TextColor::Red, &format!("{}\n", e)));
+                return None;
+            }
+        };
+
+        if self.yes_no_dialog("Refuse the digest if it is not signed?") {
+            Some(SignaturePolicy::Require(key))
+        } else {
+            Some(SignaturePolicy::VerifyIfSigned(key))
+        }
+    }
+
+    fn signing_key_dialog(&self) -> Option<SigningKey> {
+        loop {
+            println!("Enter the path to an Ed25519 private key to sign the digest file with, or press enter to not sign:");
+            let mut input = String::new();
+            if std::io::stdin().read_line(&mut input).is_err() {
+                return None;
+            }
+
+            let key_path = input.trim();
+            if key_path.is_empty() {
+                return None;
+            }
+
+            match load_signing_key(Path::new(key_path)) {
+                Ok(key) => return Some(key),
+                Err(e) => println!("{}", colorize_txt(TextColor::Red, &format!("{}\n", e))),
+            }
+        }
+    }
+
+    fn generate_key_pair_cui(&self) {
+        println!("Enter the path to save the new Ed25519 private key to (the public key is saved next to it, with '.pub' appended):");
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).is_err() {
+            println!(
+                "{}",
+                colorize_txt(
+                    TextColor::Red,
+                    "Invalid input. Please enter a valid path.\n"
+                )
+            );
+            return;
+        }
+
+        match generate_key_pair(Path::new(input.trim())) {
+            Ok(public_key_path) => println!(
+                "{}",
+                colorize_txt(
+                    TextColor::Green,
+                    &format!(
+                        "Key pair generated. Share the public key {} with whoever validates your digests, and keep the private key secret.\n",
+                        public_key_path.display()
+                    )
+                )
+            ),
+            Err(e) => println!("{}", colorize_txt(TextColor::Red, &format!("{}\n", e))),
+        }
+    }
+
+    fn save_digest_file_dialog(
+        &self,
+        engine: &mut Box<dyn EngineAny>,
+        format: DigestFormat,
+        compression: Compression,
+    ) {
+        let signing_key = self.signing_key_dialog();
+        engine.set_signing_key(signing_key);
+
         loop {
             println!("Enter the path to save the digest file: (or press enter to save in the base directory, or 'd' to discard)");
             let mut input = String::new();
@@ -347,7 +795,7 @@ impl Sfisum {
                 return;
             }
 
-            match engine.save_dd_file(Some(path.into())) {
+            match engine.save_dd_file(Some(path.into()), format, compression) {
                 Ok(_) => {
                     println!(
                         "{}",
@@ -392,7 +840,10 @@ impl Sfisum {
         engine.set_paths(PathBuf::from(digest_path), PathBuf::new());
 
         match engine.start_find_duplicates() {
-            Ok(_) => println!("{}", colorize_txt(TextColor::Green, "Digest analyzed.\n")),
+            Ok(_) => {
+                println!("{}", colorize_txt(TextColor::Green, "Digest analyzed.\n"));
+                self.print_digest_header(engine.as_ref());
+            }
             Err(e) => {
                 println!(
                     "{}",
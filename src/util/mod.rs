@@ -1 +1,2 @@
-pub mod console_text_formatter;
\ No newline at end of file
+pub mod console_text_formatter;
+pub mod varint;
\ No newline at end of file
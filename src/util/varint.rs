@@ -0,0 +1,104 @@
+use std::io;
+use std::io::{Read, Write};
+
+/// Writes an unsigned LEB128 varint - 7 bits per byte, high bit set on all but the last byte
+pub fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    let mut buffer = [0u8; 10];
+    let mut len = 0;
+
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            buffer[len] = byte;
+            len += 1;
+            break;
+        }
+
+        buffer[len] = byte | 0x80;
+        len += 1;
+    }
+
+    writer.write_all(&buffer[..len])
+}
+
+pub fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut value = 0u64;
+    let mut byte = [0u8; 1];
+
+    for shift in (0..64).step_by(7) {
+        reader.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Varint is longer than 64 bits",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(value: u64) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_varint(&mut data, value).unwrap();
+        data
+    }
+
+    #[test]
+    fn round_trip() {
+        for value in [
+            0,
+            1,
+            127,
+            128,
+            300,
+            16_383,
+            16_384,
+            u32::MAX as u64,
+            u64::MAX,
+        ] {
+            let data = encode(value);
+            assert_eq!(read_varint(&mut data.as_slice()).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn encoded_length() {
+        assert_eq!(encode(0), [0]);
+        assert_eq!(encode(127), [0x7f]);
+        assert_eq!(encode(128), [0x80, 0x01]);
+        assert_eq!(encode(300), [0xac, 0x02]);
+        assert_eq!(encode(u64::MAX).len(), 10);
+    }
+
+    #[test]
+    fn consecutive_values() {
+        let mut data = encode(300);
+        data.extend(encode(5));
+        let mut reader = data.as_slice();
+
+        assert_eq!(read_varint(&mut reader).unwrap(), 300);
+        assert_eq!(read_varint(&mut reader).unwrap(), 5);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        let error = read_varint(&mut [0x80u8, 0x80].as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn longer_than_64_bits() {
+        let error = read_varint(&mut [0x80u8; 11].as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}